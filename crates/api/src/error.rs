@@ -0,0 +1,85 @@
+use oxidized_entity::sea_orm::{DbErr, SqlErr};
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use serde_json::json;
+
+// Errors the JSON routes return, answered with the matching status and `{"error": ...}`
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized,
+    NotFound(String),
+    Conflict(String),
+    Internal,
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized => Status::Unauthorized,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(_) => Status::Conflict,
+            ApiError::Internal => Status::InternalServerError,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message) => message,
+            ApiError::Unauthorized => "Invalid API key",
+            ApiError::Internal => "Internal server error",
+        }
+    }
+}
+
+// Database details are logged rather than sent to the client
+impl From<DbErr> for ApiError {
+    fn from(err: DbErr) -> Self {
+        if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+            return ApiError::Conflict("Already exists".to_string());
+        }
+
+        error!("Database error: {:?}", err);
+
+        ApiError::Internal
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+
+        (status, Json(json!({ "error": self.message() }))).respond_to(request)
+    }
+}
+
+// Guards and Rocket itself fail with a bare status, these give those the same body
+#[catch(400)]
+pub fn bad_request() -> ApiError {
+    ApiError::BadRequest("Bad request".to_string())
+}
+
+#[catch(401)]
+pub fn unauthorized() -> ApiError {
+    ApiError::Unauthorized
+}
+
+#[catch(404)]
+pub fn not_found() -> ApiError {
+    ApiError::NotFound("Not found".to_string())
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> ApiError {
+    ApiError::BadRequest("Invalid request body".to_string())
+}
+
+#[catch(500)]
+pub fn internal_server_error() -> ApiError {
+    ApiError::Internal
+}
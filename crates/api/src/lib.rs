@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate rocket;
 
+mod error;
 mod guards;
 mod pool;
 mod routes;
@@ -8,26 +9,58 @@ mod service;
 
 use pool::*;
 use routes::*;
-use service::{misc_tasks::MiscTasksService, torrents::TorrentService, *};
+use service::*;
+
+pub use service::{misc_tasks::MiscTasksService, supervisor::Supervisor, torrents::TorrentService};
 
 use rocket::fairing::AdHoc;
 use sea_orm_rocket::Database;
-use tracing::Level;
-
-#[rocket::main]
-pub async fn main() -> Result<(), rocket::Error> {
-    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
-
-    rocket::build()
-        .attach(Db::init())
-        .attach(AdHoc::try_on_ignite(
-            "Migrations",
-            migrations::run_migrations,
-        ))
-        .attach(TorrentService::new().await)
-        .attach(MiscTasksService::new())
-        .mount("/", get_routes())
-        .launch()
-        .await
-        .map(|_| ())
+
+// Starts the HTTP API. Unless `http_only` is set the spider, workers and maintenance tasks
+// enabled in the config run alongside it.
+pub fn serve(http_only: bool) -> anyhow::Result<()> {
+    rocket::execute(async move {
+        let metrics = routes::metrics::install_recorder();
+        let supervisor = Supervisor::new();
+
+        let mut rocket = rocket::build()
+            .manage(metrics)
+            .manage(supervisor.clone())
+            .attach(Db::init())
+            .attach(AdHoc::try_on_ignite(
+                "Migrations",
+                migrations::run_migrations,
+            ))
+            .attach(AdHoc::on_shutdown("Background Tasks", |rocket| {
+                Box::pin(async move {
+                    if let Some(supervisor) = rocket.state::<Supervisor>() {
+                        supervisor.shutdown().await;
+                    }
+                })
+            }));
+
+        if !http_only {
+            rocket = rocket
+                .attach(TorrentService::new(supervisor.clone()))
+                .attach(MiscTasksService::new(supervisor));
+        }
+
+        rocket
+            .mount("/", get_routes())
+            .register(
+                "/",
+                catchers![
+                    error::bad_request,
+                    error::unauthorized,
+                    error::not_found,
+                    error::unprocessable_entity,
+                    error::internal_server_error,
+                ],
+            )
+            .register("/api", catchers![torznab::catcher])
+            .launch()
+            .await?;
+
+        Ok(())
+    })
 }
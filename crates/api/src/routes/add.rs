@@ -1,14 +1,16 @@
+use oxidized_entity::info_hash::InfoHash;
 use oxidized_entity::sea_orm::TryIntoModel;
-use oxidized_service::Mutation;
+use oxidized_service::{Mutation, Query};
 use rocket::serde::json::Json;
 use sea_orm_rocket::Connection;
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{guards::apikey::ApiKeyGuard, pool::Db};
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
 
 #[derive(Deserialize)]
 pub struct TorrentInput {
+    // a v1 or v2 info hash, or a magnet URI
     info_hash: String,
 }
 
@@ -17,22 +19,21 @@ pub async fn route(
     _apikey: ApiKeyGuard,
     conn: Connection<'_, Db>,
     torrent_input: Json<TorrentInput>,
-) -> Json<serde_json::Value> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let db = conn.into_inner();
 
-    let torrent =
-        Mutation::create_torrent(db, torrent_input.info_hash.clone().to_uppercase()).await;
+    let info_hash = InfoHash::parse(&torrent_input.info_hash)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?
+        .to_string();
 
-    if let Err(err) = torrent {
-        return Json(json!({
-            "error": format!("{}", err),
-        }));
+    if Query::exists_torrent_by_info_hash(db, &info_hash).await? {
+        return Err(ApiError::Conflict("Torrent already exists".to_string()));
     }
 
-    let torrent = torrent.unwrap();
-    let torrent = torrent.try_into_model().unwrap();
+    let torrent = Mutation::create_torrent(db, info_hash).await?;
+    let torrent = torrent.try_into_model()?;
 
-    Json(json!({
+    Ok(Json(json!({
         "torrent": torrent,
-    }))
+    })))
 }
@@ -0,0 +1,174 @@
+use oxidized_entity::info_hash::InfoHash;
+use oxidized_entity::torrent::File;
+use oxidized_service::Mutation;
+use oxidized_torrent::metadata::Metadata;
+use oxidized_torrent::torrent_file;
+use rocket::data::{Data, ToByteUnit};
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::serde::json::Json;
+use sea_orm_rocket::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
+use tokio::io::AsyncReadExt;
+
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
+
+const MAX_ENTRIES: usize = 10_000;
+const MAX_TEXT_SIZE_MIB: u8 = 4;
+
+#[derive(Serialize, Default)]
+pub struct BulkResult {
+    inserted: u64,
+    duplicate: u64,
+    invalid: u64,
+}
+
+#[derive(FromForm)]
+pub struct TorrentUpload<'r> {
+    files: Vec<TempFile<'r>>,
+}
+
+// A JSON array of info hashes or magnet URIs
+#[post("/add/bulk", format = "application/json", data = "<entries>")]
+pub async fn json(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    entries: Json<Vec<String>>,
+) -> Result<Json<BulkResult>, ApiError> {
+    add_info_hashes(conn, entries.into_inner()).await
+}
+
+// One info hash or magnet URI per line
+#[post("/add/bulk", format = "text/plain", data = "<data>")]
+pub async fn text(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    data: Data<'_>,
+) -> Result<Json<BulkResult>, ApiError> {
+    let text = data
+        .open(MAX_TEXT_SIZE_MIB.mebibytes())
+        .into_string()
+        .await
+        .map_err(|_| ApiError::BadRequest("Body is not valid UTF-8".to_string()))?;
+
+    if !text.is_complete() {
+        return Err(ApiError::BadRequest(format!(
+            "Body is larger than {} MiB",
+            MAX_TEXT_SIZE_MIB
+        )));
+    }
+
+    let entries = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    add_info_hashes(conn, entries).await
+}
+
+// .torrent files uploaded as `files`, their metadata is stored right away so they skip the
+// info queue
+#[post("/add/bulk", format = "multipart/form-data", data = "<upload>")]
+pub async fn files(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    upload: Form<TorrentUpload<'_>>,
+) -> Result<Json<BulkResult>, ApiError> {
+    let db = conn.into_inner();
+
+    if upload.files.len() > MAX_ENTRIES {
+        return Err(too_many_entries());
+    }
+
+    let mut result = BulkResult::default();
+
+    for file in &upload.files {
+        let bytes = read_file(file).await.map_err(|e| {
+            error!("Cannot read uploaded torrent: {:?}", e);
+            ApiError::Internal
+        })?;
+
+        let Ok(info) = torrent_file::extract_info(&bytes) else {
+            result.invalid += 1;
+            continue;
+        };
+
+        let Ok(metadata) = Metadata::from_info(&info) else {
+            result.invalid += 1;
+            continue;
+        };
+
+        let size = metadata.files.iter().map(|file| file.length).sum();
+        let files = metadata
+            .files
+            .into_iter()
+            .map(|file| File::new(file.path, file.length))
+            .collect();
+
+        let created = Mutation::create_torrent_with_metadata(
+            db,
+            torrent_file::info_hash(&info),
+            metadata.name,
+            size,
+            files,
+            info,
+        )
+        .await?;
+
+        match created {
+            Some(_) => result.inserted += 1,
+            None => result.duplicate += 1,
+        }
+    }
+
+    Ok(Json(result))
+}
+
+async fn add_info_hashes(
+    conn: Connection<'_, Db>,
+    entries: Vec<String>,
+) -> Result<Json<BulkResult>, ApiError> {
+    let db = conn.into_inner();
+
+    if entries.len() > MAX_ENTRIES {
+        return Err(too_many_entries());
+    }
+
+    let mut result = BulkResult::default();
+    let mut info_hashes = HashSet::new();
+
+    for entry in &entries {
+        match InfoHash::parse(entry) {
+            Ok(info_hash) => {
+                if !info_hashes.insert(info_hash.to_string()) {
+                    result.duplicate += 1;
+                }
+            }
+            Err(_) => result.invalid += 1,
+        }
+    }
+
+    let valid = info_hashes.len() as u64;
+
+    result.inserted = Mutation::create_torrents(db, info_hashes.into_iter().collect()).await?;
+    result.duplicate += valid - result.inserted;
+
+    Ok(Json(result))
+}
+
+async fn read_file(file: &TempFile<'_>) -> std::io::Result<Vec<u8>> {
+    let reader = file.open().await?;
+    tokio::pin!(reader);
+
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).await?;
+
+    Ok(bytes)
+}
+
+fn too_many_entries() -> ApiError {
+    ApiError::BadRequest(format!("At most {} entries per request", MAX_ENTRIES))
+}
@@ -4,32 +4,27 @@ use sea_orm_rocket::Connection;
 use serde_json::json;
 use std::time::Instant;
 
-use crate::{guards::apikey::ApiKeyGuard, pool::Db};
+use super::torrent_json;
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
 
 #[get("/<info_hash>")]
 pub async fn route(
     _apikey: ApiKeyGuard,
     conn: Connection<'_, Db>,
     info_hash: String,
-) -> Json<serde_json::Value> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let db = conn.into_inner();
 
     let start = Instant::now();
 
-    let torrent = Query::find_torrent_by_info_hash(db, info_hash).await;
+    let torrent = Query::find_torrent_by_info_hash(db, info_hash)
+        .await?
+        .ok_or(ApiError::NotFound("Torrent not found".to_string()))?;
 
     let duration = start.elapsed();
 
-    if let Err(err) = torrent {
-        return Json(json!({
-            "error": format!("{}", err),
-        }));
-    }
-
-    let torrent = torrent.unwrap();
-
-    Json(json!({
-        "torrent": torrent,
+    Ok(Json(json!({
+        "torrent": torrent_json(&torrent),
         "speed": duration.as_micros() as f64 / 1000.0,
-    }))
+    })))
 }
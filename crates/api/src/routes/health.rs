@@ -0,0 +1,133 @@
+use chrono::Utc;
+use oxidized_migration::{Migrator, MigratorTrait};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use sea_orm_rocket::Connection;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::{
+    pool::Db,
+    service::supervisor::{Supervisor, TaskHealth, TaskState, HEARTBEAT_TIMEOUT},
+};
+
+// Tasks that must keep heartbeating while they run, the others only report failures
+const WORKERS: &[&str] = &["producer", "info", "trackers", "spider", "samples"];
+
+#[derive(Serialize)]
+struct Component {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl Component {
+    fn ok() -> Self {
+        Component {
+            status: "ok",
+            reason: None,
+        }
+    }
+
+    fn degraded(reason: impl Into<String>) -> Self {
+        Component {
+            status: "degraded",
+            reason: Some(reason.into()),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.reason.is_none()
+    }
+}
+
+// The process is up and answering requests
+#[get("/health/live")]
+pub async fn live() -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "alive",
+    }))
+}
+
+// The database is reachable and migrated, and the background tasks of this process are
+// running and heartbeating. Any degraded component makes it 503.
+#[get("/health/ready")]
+pub async fn ready(
+    conn: Connection<'_, Db>,
+    supervisor: &State<Supervisor>,
+) -> (Status, Json<serde_json::Value>) {
+    let db = conn.into_inner();
+    let mut components = BTreeMap::new();
+
+    let database = match db.ping().await {
+        Ok(_) => Component::ok(),
+        Err(e) => Component::degraded(e.to_string()),
+    };
+
+    let migrations = match database.is_ok() {
+        true => match Migrator::get_pending_migrations(db).await {
+            Ok(pending) if pending.is_empty() => Component::ok(),
+            Ok(pending) => Component::degraded(format!("{} pending migrations", pending.len())),
+            Err(e) => Component::degraded(e.to_string()),
+        },
+        false => Component::degraded("Database is unreachable"),
+    };
+
+    components.insert("database", database);
+    components.insert("migrations", migrations);
+
+    for (name, health) in supervisor.health() {
+        components.insert(name, task_component(name, &health));
+    }
+
+    let ready = components.values().all(Component::is_ok);
+
+    let status = match ready {
+        true => Status::Ok,
+        false => Status::ServiceUnavailable,
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "degraded" },
+            "components": components,
+        })),
+    )
+}
+
+fn task_component(name: &str, health: &TaskHealth) -> Component {
+    let worker = WORKERS.contains(&name);
+
+    match health.state {
+        TaskState::Restarting => {
+            return Component::degraded(format!(
+                "Restarting after {} failures, last: {}",
+                health.restarts,
+                health.last_error.as_deref().unwrap_or("unknown")
+            ));
+        }
+        TaskState::Stopped if worker => return Component::degraded("Stopped"),
+        _ => {}
+    }
+
+    if !worker || health.state == TaskState::Stopped {
+        return Component::ok();
+    }
+
+    if let Some(reason) = &health.degraded {
+        return Component::degraded(reason.clone());
+    }
+
+    let silent = health
+        .last_heartbeat
+        .map(|at| (Utc::now().naive_utc() - at).to_std().unwrap_or_default())
+        .unwrap_or(HEARTBEAT_TIMEOUT);
+
+    match silent >= HEARTBEAT_TIMEOUT {
+        true => Component::degraded(format!("No heartbeat for {}s", silent.as_secs())),
+        false => Component::ok(),
+    }
+}
@@ -0,0 +1,42 @@
+use oxidized_service::Query;
+use rocket::serde::json::Json;
+use sea_orm_rocket::Connection;
+use serde_json::json;
+use std::time::Instant;
+
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
+
+// ranked after /torrent/<file>, which has the same shape
+#[get("/<info_hash>/history?<days>", rank = 2)]
+pub async fn route(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    info_hash: String,
+    days: Option<i64>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let db = conn.into_inner();
+
+    let start = Instant::now();
+
+    let since = days
+        .map(|days| {
+            chrono::Duration::try_days(days)
+                .filter(|_| days > 0)
+                .and_then(|days| chrono::Utc::now().naive_utc().checked_sub_signed(days))
+                .ok_or(ApiError::BadRequest("days is out of range".to_string()))
+        })
+        .transpose()?;
+
+    let torrent = Query::find_torrent_by_info_hash(db, info_hash)
+        .await?
+        .ok_or(ApiError::NotFound("Torrent not found".to_string()))?;
+
+    let history = Query::find_swarm_history(db, torrent.id, since).await?;
+
+    let duration = start.elapsed();
+
+    Ok(Json(json!({
+        "history": history,
+        "speed": duration.as_micros() as f64 / 1000.0,
+    })))
+}
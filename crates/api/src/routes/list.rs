@@ -3,7 +3,8 @@ use rocket::serde::json::Json;
 use sea_orm_rocket::Connection;
 use serde_json::json;
 
-use crate::{guards::apikey::ApiKeyGuard, pool::Db};
+use super::torrent_json;
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
 
 const DEFAULT_POSTS_PER_PAGE: u64 = 100;
 
@@ -13,22 +14,29 @@ pub async fn route(
     conn: Connection<'_, Db>,
     page: Option<u64>,
     posts_per_page: Option<u64>,
-) -> Json<serde_json::Value> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let db = conn.into_inner();
 
     // Set page number and items per page
     let page = page.unwrap_or(1);
     let posts_per_page = posts_per_page.unwrap_or(DEFAULT_POSTS_PER_PAGE);
+
     if page == 0 {
-        panic!("Page number cannot be zero");
+        return Err(ApiError::BadRequest(
+            "Page number cannot be zero".to_string(),
+        ));
+    }
+
+    if posts_per_page == 0 {
+        return Err(ApiError::BadRequest(
+            "Posts per page cannot be zero".to_string(),
+        ));
     }
 
-    let (posts, num_pages) = Query::find_torrents_in_page(db, page, posts_per_page)
-        .await
-        .expect("Cannot find posts in page");
+    let (posts, num_pages) = Query::find_torrents_in_page(db, page, posts_per_page).await?;
 
-    Json(json!({
-        "posts": posts,
+    Ok(Json(json!({
+        "posts": posts.iter().map(torrent_json).collect::<Vec<_>>(),
         "num_pages": num_pages,
-    }))
+    })))
 }
@@ -0,0 +1,108 @@
+use metrics::{describe_counter, describe_gauge, describe_histogram, gauge, Unit};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use oxidized_service::Query;
+use rocket::http::ContentType;
+use rocket::State;
+use sea_orm_rocket::Connection;
+
+use crate::pool::Db;
+
+// Seconds, sized for tracker scrapes and metadata fetches which time out after 10s
+const DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 15.0, 30.0];
+
+pub fn install_recorder() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(DURATION_BUCKETS)
+        .expect("Cannot set histogram buckets")
+        .install_recorder()
+        .expect("Cannot install metrics recorder");
+
+    describe_counter!(
+        "oxidized_spider_torrents_received_total",
+        "Torrents received from the DHT spider"
+    );
+    describe_counter!(
+        "oxidized_spider_torrents_total",
+        "Spider torrents by outcome: added, duplicate, filtered or failed"
+    );
+    describe_counter!(
+        "oxidized_spider_samples_total",
+        "Sampled info hashes by outcome: new or known"
+    );
+    describe_counter!(
+        "oxidized_info_fetch_total",
+        "Metadata fetches by outcome: success, timeout or error"
+    );
+    describe_histogram!(
+        "oxidized_info_fetch_duration_seconds",
+        Unit::Seconds,
+        "Time spent fetching torrent metadata from peers"
+    );
+    describe_histogram!(
+        "oxidized_tracker_scrape_duration_seconds",
+        Unit::Seconds,
+        "Time spent scraping a tracker"
+    );
+    describe_counter!(
+        "oxidized_tracker_scrape_failures_total",
+        "Failed tracker scrapes"
+    );
+    describe_counter!(
+        "oxidized_task_restarts_total",
+        "Background tasks restarted after failing or panicking"
+    );
+    describe_gauge!(
+        "oxidized_torrents",
+        "Torrents by state: total, scraped or stale"
+    );
+    describe_gauge!(
+        "oxidized_queue_depth",
+        "Torrents waiting for a metadata fetch (info) or a tracker scrape (trackers)"
+    );
+    describe_gauge!(
+        "oxidized_channel_depth",
+        "Items waiting in a pipeline channel: discoveries, torrents, samples, info or trackers"
+    );
+    describe_counter!(
+        "oxidized_channel_dropped_total",
+        "Items dropped because their pipeline channel was full: discoveries or samples"
+    );
+    describe_gauge!(
+        "oxidized_info_fetches_in_flight",
+        "Metadata fetches for queued torrents running at once"
+    );
+    describe_gauge!(
+        "oxidized_database_up",
+        "Whether the database answered the last stats query"
+    );
+
+    handle
+}
+
+#[get("/metrics")]
+pub async fn route(
+    conn: Connection<'_, Db>,
+    handle: &State<PrometheusHandle>,
+) -> (ContentType, String) {
+    let db = conn.into_inner();
+
+    match Query::get_stats(db).await {
+        Ok(stats) => {
+            gauge!("oxidized_database_up").set(1.0);
+
+            gauge!("oxidized_torrents", "state" => "total").set(stats.torrents as f64);
+            gauge!("oxidized_torrents", "state" => "scraped").set(stats.scraped as f64);
+            gauge!("oxidized_torrents", "state" => "stale").set(stats.stale as f64);
+
+            gauge!("oxidized_queue_depth", "queue" => "info").set(stats.queue.info as f64);
+            gauge!("oxidized_queue_depth", "queue" => "trackers").set(stats.queue.trackers as f64);
+        }
+        Err(err) => {
+            error!("Cannot get stats for metrics: {:?}", err);
+
+            gauge!("oxidized_database_up").set(0.0);
+        }
+    }
+
+    (ContentType::Plain, handle.render())
+}
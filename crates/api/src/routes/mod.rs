@@ -1,19 +1,70 @@
+use oxidized_entity::torrent::Model as Torrent;
+use oxidized_service::{Magnet, SearchSort};
 use rocket::Route;
+use serde_json::json;
 
 pub mod add;
+pub mod bulk;
 pub mod get;
+pub mod health;
+pub mod history;
 pub mod index;
 pub mod list;
+pub mod metrics;
+pub mod popular;
+pub mod search;
 pub mod stats;
+pub mod torrent;
 pub mod torznab;
+pub mod trending;
 
 pub fn get_routes() -> Vec<Route> {
     routes![
         list::route,
+        metrics::route,
+        popular::route,
+        search::route,
         add::route,
+        bulk::json,
+        bulk::text,
+        bulk::files,
         get::route,
+        health::live,
+        health::ready,
+        history::route,
         index::route,
         stats::route,
+        torrent::route,
         torznab::route,
+        trending::route,
     ]
 }
+
+// A torrent as the JSON routes return it, the stored fields plus a ready to use magnet link
+pub(crate) fn torrent_json(torrent: &Torrent) -> serde_json::Value {
+    let mut value = json!(torrent);
+
+    value["magnet"] = json!(Magnet::uri(torrent));
+
+    value
+}
+
+const DEFAULT_TRENDING_HOURS: i64 = 24;
+const MAX_TRENDING_HOURS: i64 = 24 * 30;
+
+// "popular" or "trending" with a window in hours, anything else keeps the default order
+pub(crate) fn parse_sort(sort: Option<&str>, window: Option<i64>) -> SearchSort {
+    match sort {
+        Some("popular") => SearchSort::Popular,
+        Some("trending") => SearchSort::Trending(trending_window(window)),
+        _ => SearchSort::Relevance,
+    }
+}
+
+pub(crate) fn trending_window(hours: Option<i64>) -> chrono::Duration {
+    let hours = hours
+        .unwrap_or(DEFAULT_TRENDING_HOURS)
+        .clamp(1, MAX_TRENDING_HOURS);
+
+    chrono::Duration::try_hours(hours).unwrap()
+}
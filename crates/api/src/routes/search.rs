@@ -0,0 +1,69 @@
+use oxidized_service::{Query, SearchQuery};
+use rocket::serde::json::Json;
+use sea_orm_rocket::Connection;
+use serde_json::json;
+use std::time::Instant;
+
+use super::{parse_sort, torrent_json};
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
+
+pub const DEFAULT_LIMIT: u64 = 50;
+pub const MAX_LIMIT: u64 = 100;
+
+// Comma separated extensions such as "mkv,.mp4", matched without the dot and case
+pub fn parse_extensions(ext: Option<&str>) -> Option<Vec<String>> {
+    let extensions = ext?
+        .split(',')
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect::<Vec<String>>();
+
+    if extensions.is_empty() {
+        return None;
+    }
+
+    Some(extensions)
+}
+
+#[derive(FromForm, Debug)]
+pub struct SearchParams {
+    q: Option<String>,
+    ext: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    boost_seeders: Option<bool>,
+    sort: Option<String>,
+    window: Option<i64>,
+}
+
+#[get("/search?<params..>")]
+pub async fn route(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    params: SearchParams,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let db = conn.into_inner();
+
+    let start = Instant::now();
+
+    let torrents = Query::search_torrents(
+        db,
+        SearchQuery {
+            query: params.q,
+            extensions: parse_extensions(params.ext.as_deref()),
+            offset: params.offset,
+            limit: Some(params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)),
+            boost_seeders: params.boost_seeders.unwrap_or(true),
+            sort: parse_sort(params.sort.as_deref(), params.window),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let duration = start.elapsed();
+
+    Ok(Json(json!({
+        "torrents": torrents.iter().map(torrent_json).collect::<Vec<_>>(),
+        "speed": duration.as_micros() as f64 / 1000.0,
+    })))
+}
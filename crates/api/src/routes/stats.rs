@@ -1,31 +1,29 @@
 use oxidized_service::Query;
 use rocket::serde::json::Json;
+use rocket::State;
 use sea_orm_rocket::Connection;
 use serde_json::json;
 use tokio::time::Instant;
 
-use crate::pool::Db;
+use crate::{error::ApiError, pool::Db, service::supervisor::Supervisor};
 
 #[get("/stats")]
-pub async fn route(conn: Connection<'_, Db>) -> Json<serde_json::Value> {
+pub async fn route(
+    conn: Connection<'_, Db>,
+    supervisor: &State<Supervisor>,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let db = conn.into_inner();
 
     let start = Instant::now();
 
-    let stats = Query::get_stats(db).await;
+    let stats = Query::get_stats(db).await?;
 
     let duration = start.elapsed();
 
-    if let Err(err) = stats {
-        return Json(json!({
-            "error": format!("{}", err),
-        }));
-    }
-
-    let stats = stats.unwrap();
-
-    Json(json!({
+    Ok(Json(json!({
         "stats": stats,
         "speed": duration.as_micros() as f64 / 1000.0,
-    }))
+        // background tasks of this process, empty when it only serves HTTP
+        "workers": supervisor.health(),
+    })))
 }
@@ -0,0 +1,39 @@
+use oxidized_service::{Magnet, Query};
+use oxidized_torrent::torrent_file;
+use rocket::http::ContentType;
+use sea_orm_rocket::Connection;
+
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
+
+#[get("/torrent/<file>")]
+pub async fn route(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    file: &str,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let db = conn.into_inner();
+
+    let info_hash = file
+        .strip_suffix(".torrent")
+        .ok_or(ApiError::NotFound("Not found".to_string()))?;
+
+    let torrent = Query::find_torrent_by_info_hash(db, info_hash.to_string())
+        .await?
+        .ok_or(ApiError::NotFound("Torrent not found".to_string()))?;
+
+    let metadata =
+        Query::find_torrent_metadata(db, torrent.id)
+            .await?
+            .ok_or(ApiError::NotFound(
+                "Torrent metadata not fetched yet".to_string(),
+            ))?;
+
+    let trackers = Magnet::best_trackers(&torrent.trackers);
+
+    let file = torrent_file::build(&metadata.info, &trackers).map_err(|e| {
+        error!("Cannot build torrent file for {}: {:?}", info_hash, e);
+        ApiError::Internal
+    })?;
+
+    Ok((ContentType::new("application", "x-bittorrent"), file))
+}
@@ -1,13 +1,19 @@
+use std::collections::HashSet;
 use std::io::Cursor;
 
+use oxidized_config::get_config;
 use oxidized_entity::torrent::Model as Torrent;
-use oxidized_service::Query;
+use oxidized_service::{encode_param, Magnet, Query, SearchQuery};
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
 use rocket::http::uri::Host;
 use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::Request;
 use sea_orm_rocket::Connection;
 
+use super::parse_sort;
+use super::search::parse_extensions;
 use crate::guards::apikey::ApiKeyGuard;
 use crate::Db;
 
@@ -17,15 +23,109 @@ pub struct TorznabQuery<'a> {
     q: Option<String>,
     offset: Option<u64>,
     limit: Option<u64>,
+    season: Option<String>,
+    ep: Option<String>,
+    year: Option<String>,
+    tvdbid: Option<String>,
+    imdbid: Option<String>,
+    cat: Option<String>,
+    ext: Option<String>,
+    // not part of Torznab, lets the empty query feed be ranked by "popular" or "trending"
+    sort: Option<String>,
+    window: Option<i64>,
 }
 
-// let categories_to_add = vec![("8000", "Other"), ("2000", "Movies"), ("5000", "TV")];
-static CATEGORIES_TO_ADD: &[(&str, &str, &[(&str, &str)])] = &[
+// Torznab search functions advertised in caps, with the parameters each supports
+static SEARCH_MODES: &[(&str, &str)] = &[
+    ("search", "q"),
+    ("tv-search", "q,season,ep"),
+    ("movie-search", "q,year"),
+];
+
+type Category = (
+    &'static str,
+    &'static str,
+    &'static [(&'static str, &'static str)],
+);
+
+static CATEGORIES_TO_ADD: &[Category] = &[
     ("8000", "Other", &[("8010", "Other/Misc")]),
     ("2000", "Movies", &[]),
+    ("3000", "Audio", &[]),
+    ("4000", "PC", &[]),
     ("5000", "TV", &[("5040", "TV/HD"), ("5070", "TV/SD")]),
+    ("6000", "XXX", &[]),
+    ("7000", "Books", &[]),
 ];
 
+// The <error code=... description=...> answer Torznab clients expect instead of a feed
+#[derive(Debug)]
+pub struct TorznabError {
+    status: Status,
+    code: u16,
+    description: &'static str,
+}
+
+impl TorznabError {
+    pub const INCORRECT_CREDENTIALS: TorznabError = TorznabError {
+        status: Status::Unauthorized,
+        code: 100,
+        description: "Incorrect user credentials",
+    };
+    pub const INCORRECT_PARAMETER: TorznabError = TorznabError {
+        status: Status::BadRequest,
+        code: 201,
+        description: "Incorrect parameter",
+    };
+    pub const NO_SUCH_FUNCTION: TorznabError = TorznabError {
+        status: Status::BadRequest,
+        code: 202,
+        description: "No such function",
+    };
+    pub const UNKNOWN_ERROR: TorznabError = TorznabError {
+        status: Status::InternalServerError,
+        code: 900,
+        description: "Unknown error",
+    };
+
+    fn from_status(status: Status) -> Self {
+        match status.code {
+            401 => TorznabError::INCORRECT_CREDENTIALS,
+            400 | 422 => TorznabError::INCORRECT_PARAMETER,
+            404 => TorznabError::NO_SUCH_FUNCTION,
+            _ => TorznabError::UNKNOWN_ERROR,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for TorznabError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+
+        let mut element = BytesStart::new("error");
+        element.push_attribute(("code", self.code.to_string().as_str()));
+        element.push_attribute(("description", self.description));
+
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+            .and_then(|_| writer.write_event(Event::Empty(element)))
+            .map_err(|e| {
+                error!("Cannot write Torznab error: {:?}", e);
+                Status::InternalServerError
+            })?;
+
+        let xml = String::from_utf8(writer.into_inner().into_inner()).unwrap();
+
+        (self.status, (ContentType::XML, xml)).respond_to(request)
+    }
+}
+
+// Registered under /api so failed guards and bad parameters get a Torznab error too
+#[catch(default)]
+pub fn catcher(status: Status, _request: &Request) -> TorznabError {
+    TorznabError::from_status(status)
+}
+
 fn generate_caps_response() -> String {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
 
@@ -52,11 +152,13 @@ fn generate_caps_response() -> String {
     let searching = BytesStart::new("searching");
     writer.write_event(Event::Start(searching)).unwrap();
 
-    let mut search = BytesStart::new("search");
-    search.push_attribute(("available", "yes"));
-    search.push_attribute(("supportedParams", "q"));
+    for (mode, params) in SEARCH_MODES {
+        let mut search = BytesStart::new(*mode);
+        search.push_attribute(("available", "yes"));
+        search.push_attribute(("supportedParams", *params));
 
-    writer.write_event(Event::Empty(search)).unwrap();
+        writer.write_event(Event::Empty(search)).unwrap();
+    }
 
     writer
         .write_event(Event::End(BytesEnd::new("searching")))
@@ -105,7 +207,63 @@ fn generate_caps_response() -> String {
     String::from_utf8(writer.into_inner().into_inner()).unwrap()
 }
 
-fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::Result<String> {
+// Matches S01E02 and 1x02 style names, just the season when no episode is given, or
+// air dates for daily shows where the season is the year and the episode is "MM/DD"
+fn episode_pattern(season: Option<&str>, ep: Option<&str>) -> Option<String> {
+    let season = season?.trim();
+
+    if let Some((month, day)) = ep.and_then(|ep| ep.split_once('/')) {
+        let (year, month, day) = (
+            season.parse::<u32>().ok()?,
+            month.parse::<u32>().ok()?,
+            day.parse::<u32>().ok()?,
+        );
+
+        return Some(format!(
+            "(^|[^0-9]){}[ ._-]?{:02}[ ._-]?{:02}([^0-9]|$)",
+            year, month, day
+        ));
+    }
+
+    let season = season.parse::<u32>().ok()?;
+
+    match ep.and_then(|ep| ep.trim().parse::<u32>().ok()) {
+        Some(ep) => Some(format!(
+            "(^|[^a-z0-9])s0*{season}[ ._-]?e0*{ep}([^0-9]|$)|(^|[^0-9]){season}x0*{ep}([^0-9]|$)"
+        )),
+        None => Some(format!(
+            "(^|[^a-z0-9])s0*{season}([^0-9]|$)|season[ ._-]*0*{season}([^0-9]|$)"
+        )),
+    }
+}
+
+// Only parent categories are stored, so subcategories such as 5040 match their parent 5000
+fn parse_categories(cat: Option<&str>) -> Option<Vec<i32>> {
+    let categories = cat?
+        .split(',')
+        .filter_map(|id| id.trim().parse::<i32>().ok())
+        .map(|id| id - id % 1000)
+        .collect::<Vec<i32>>();
+
+    if categories.is_empty() {
+        return None;
+    }
+
+    Some(categories)
+}
+
+fn year_pattern(year: Option<&str>) -> Option<String> {
+    let year = year?.trim().parse::<u32>().ok()?;
+
+    Some(format!("(^|[^0-9]){}([^0-9]|$)", year))
+}
+
+// Torrents in `with_metadata` link to their .torrent file, the others only to their magnet URI
+fn generate_search_response(
+    origin: &Host,
+    torrents: Vec<&Torrent>,
+    with_metadata: &HashSet<i32>,
+) -> anyhow::Result<String> {
     let mut writer = Writer::new(Cursor::new(Vec::new()));
 
     writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
@@ -138,8 +296,27 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
         .create_element("ttl")
         .write_text_content(BytesText::new("30"))?;
 
+    // download links are followed without our query string, so they carry the key themselves
+    let apikey = get_config()
+        .auth
+        .apikey
+        .map(|apikey| format!("?apikey={}", encode_param(&apikey)))
+        .unwrap_or_default();
+
     // Iterate through torrents to add items
     for torrent in torrents {
+        let magnet = Magnet::uri(torrent);
+        let (torrent_url, enclosure_type) = match with_metadata.contains(&torrent.id) {
+            true => (
+                format!(
+                    "http://{}/torrent/{}.torrent{}",
+                    origin, torrent.info_hash, apikey
+                ),
+                "application/x-bittorrent",
+            ),
+            false => (magnet.clone(), "x-scheme-handler/magnet"),
+        };
+
         let item = BytesStart::new("item");
 
         writer.write_event(Event::Start(item))?;
@@ -149,34 +326,24 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
             .write_text_content(BytesText::new(torrent.name.as_ref().unwrap().as_str()))?;
         writer
             .create_element("link")
-            .write_text_content(BytesText::new(
-                format!(
-                    "https://itorrents.org/torrent/{}.torrent",
-                    torrent.info_hash
-                )
-                .as_str(),
-            ))?;
+            .write_text_content(BytesText::new(torrent_url.as_str()))?;
         writer
             .create_element("description")
             .write_text_content(BytesText::new(
-                format!("Total Size: {} MB", torrent.size).as_str(),
+                format!("Total Size: {:.2} MB", torrent.size as f64 / 1000000.0).as_str(),
             ))?;
         writer
             .create_element("pubDate")
             .write_text_content(BytesText::new(torrent.added_at.to_string().as_str()))?;
         writer
             .create_element("size")
-            .write_text_content(BytesText::new(
-                (torrent.size as i64 * 1000000).to_string().as_str(),
-            ))?;
+            .write_text_content(BytesText::new(torrent.size.to_string().as_str()))?;
         writer
             .create_element("infohash")
             .write_text_content(BytesText::new(torrent.info_hash.as_str()))?;
         writer
             .create_element("category")
-            .write_text_content(BytesText::new(match torrent.get_category() {
-                (_, cat_id) => cat_id,
-            }))?;
+            .write_text_content(BytesText::new(torrent.category.to_string().as_str()))?;
         writer
             .create_element("seeders")
             .write_text_content(BytesText::new(torrent.seeders.to_string().as_str()))?;
@@ -187,36 +354,25 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
             ))?;
         writer
             .create_element("magneturl")
-            .write_text_content(BytesText::new(
-                format!("magnet:?xt=urn:btih:{}", torrent.info_hash.to_uppercase()).as_str(),
-            ))?;
+            .write_text_content(BytesText::new(magnet.as_str()))?;
 
         let mut enc = BytesStart::new("enclosure");
-        enc.push_attribute(("type", "application/x-bittorrent"));
-        enc.push_attribute((
-            "url",
-            format!(
-                "https://itorrents.org/torrent/{}.torrent",
-                torrent.info_hash
-            )
-            .as_str(),
-        ));
+        enc.push_attribute(("type", enclosure_type));
+        enc.push_attribute(("url", torrent_url.as_str()));
+        enc.push_attribute(("length", torrent.size.to_string().as_str()));
 
         writer.write_event(Event::Empty(enc)).unwrap();
 
         writer
             .create_element("torznab:attr")
             .with_attribute(("name", "files"))
-            .with_attribute(("value", torrent.files.len().to_string().as_str()))
+            .with_attribute(("value", torrent.files.0.len().to_string().as_str()))
             .write_empty()?;
 
         writer
             .create_element("torznab:attr")
             .with_attribute(("name", "size"))
-            .with_attribute((
-                "value",
-                (torrent.size as i64 * 1000000).to_string().as_str(),
-            ))
+            .with_attribute(("value", torrent.size.to_string().as_str()))
             .write_empty()?;
 
         writer
@@ -228,10 +384,7 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
         writer
             .create_element("torznab:attr")
             .with_attribute(("name", "magneturl"))
-            .with_attribute((
-                "value",
-                format!("magnet:?xt=urn:btih:{}", torrent.info_hash.to_uppercase()).as_str(),
-            ))
+            .with_attribute(("value", magnet.as_str()))
             .write_empty()?;
 
         writer
@@ -252,14 +405,35 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
         writer
             .create_element("torznab:attr")
             .with_attribute(("name", "category"))
-            .with_attribute((
-                "value",
-                match torrent.get_category() {
-                    (_, cat_id) => cat_id,
-                },
-            ))
+            .with_attribute(("value", torrent.category.to_string().as_str()))
             .write_empty()?;
 
+        if let Some(release) = &torrent.release {
+            let attrs = [
+                ("season", release.season.map(|season| season.to_string())),
+                (
+                    "episode",
+                    release.episode.map(|episode| episode.to_string()),
+                ),
+                ("year", release.year.map(|year| year.to_string())),
+                ("resolution", release.resolution.clone()),
+                ("video", release.video_codec.clone()),
+                ("audio", release.audio.clone()),
+                ("language", release.language.clone()),
+                ("team", release.group.clone()),
+            ];
+
+            for (name, value) in attrs {
+                if let Some(value) = value {
+                    writer
+                        .create_element("torznab:attr")
+                        .with_attribute(("name", name))
+                        .with_attribute(("value", value.as_str()))
+                        .write_empty()?;
+                }
+            }
+        }
+
         writer.write_event(Event::End(BytesEnd::new("item")))?;
     }
 
@@ -273,33 +447,67 @@ fn generate_search_response(origin: &Host, torrents: Vec<&Torrent>) -> anyhow::R
 }
 
 #[get("/api?<query..>")]
-pub async fn route<'a>(
+pub async fn route(
     _apikey: ApiKeyGuard,
     conn: Connection<'_, Db>,
     query: TorznabQuery<'_>,
     origin: &Host<'_>,
-) -> (Status, (ContentType, String)) {
+) -> Result<(ContentType, String), TorznabError> {
     let conn = conn.into_inner();
 
     match query.t.unwrap_or("search") {
-        "caps" => (Status::Ok, (ContentType::XML, generate_caps_response())),
-        "search" => {
-            let torrents =
-                Query::search_torrents_by_name(&conn, query.q, query.offset, query.limit)
-                    .await
-                    .expect("Cannot search torrents");
-
-            (
-                Status::Ok,
-                (
-                    ContentType::XML,
-                    generate_search_response(origin, torrents.iter().collect()).unwrap(),
-                ),
+        "caps" => Ok((ContentType::XML, generate_caps_response())),
+        t @ ("search" | "tvsearch" | "movie") => {
+            // we have no id mappings, so id-only lookups cannot match anything
+            let id_only = query.q.is_none() && (query.tvdbid.is_some() || query.imdbid.is_some());
+
+            let pattern = match t {
+                "tvsearch" => episode_pattern(query.season.as_deref(), query.ep.as_deref()),
+                "movie" => year_pattern(query.year.as_deref()),
+                _ => None,
+            };
+
+            let torrents = if id_only {
+                vec![]
+            } else {
+                Query::search_torrents(
+                    conn,
+                    SearchQuery {
+                        query: query.q,
+                        pattern,
+                        categories: parse_categories(query.cat.as_deref()),
+                        extensions: parse_extensions(query.ext.as_deref()),
+                        offset: query.offset,
+                        limit: query.limit,
+                        boost_seeders: true,
+                        sort: parse_sort(query.sort.as_deref(), query.window),
+                    },
+                )
+                .await
+                .map_err(|e| {
+                    error!("Cannot search torrents: {:?}", e);
+                    TorznabError::UNKNOWN_ERROR
+                })?
+            };
+
+            let with_metadata = Query::find_torrents_with_metadata(
+                conn,
+                torrents.iter().map(|torrent| torrent.id).collect(),
             )
+            .await
+            .map_err(|e| {
+                error!("Cannot find torrent metadata: {:?}", e);
+                TorznabError::UNKNOWN_ERROR
+            })?;
+
+            let xml = generate_search_response(origin, torrents.iter().collect(), &with_metadata)
+                .map_err(|e| {
+                error!("Cannot write search response: {:?}", e);
+                TorznabError::UNKNOWN_ERROR
+            })?;
+
+            Ok((ContentType::XML, xml))
         }
-        _ => (
-            Status::NotFound,
-            (ContentType::Text, "Not Found".to_string()),
-        ),
+        _ => Err(TorznabError::NO_SUCH_FUNCTION),
     }
 }
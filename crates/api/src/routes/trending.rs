@@ -0,0 +1,41 @@
+use oxidized_service::{Query, SearchQuery, SearchSort};
+use rocket::serde::json::Json;
+use sea_orm_rocket::Connection;
+use serde_json::json;
+use std::time::Instant;
+
+use super::search::{DEFAULT_LIMIT, MAX_LIMIT};
+use super::{torrent_json, trending_window};
+use crate::{error::ApiError, guards::apikey::ApiKeyGuard, pool::Db};
+
+// Torrents that gained the most seeders over the last `window` hours
+#[get("/trending?<window>&<offset>&<limit>")]
+pub async fn route(
+    _apikey: ApiKeyGuard,
+    conn: Connection<'_, Db>,
+    window: Option<i64>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let db = conn.into_inner();
+
+    let start = Instant::now();
+
+    let torrents = Query::search_torrents(
+        db,
+        SearchQuery {
+            offset,
+            limit: Some(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)),
+            sort: SearchSort::Trending(trending_window(window)),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let duration = start.elapsed();
+
+    Ok(Json(json!({
+        "torrents": torrents.iter().map(torrent_json).collect::<Vec<_>>(),
+        "speed": duration.as_micros() as f64 / 1000.0,
+    })))
+}
@@ -1,14 +1,22 @@
+use crate::service::supervisor::{stopped, Supervisor};
 use crate::Db;
+use anyhow::Context;
 use oxidized_config::get_config;
 use oxidized_entity::{sea_orm::prelude::ConnectionTrait, sea_orm::DatabaseConnection};
-use oxidized_service::Mutation;
+use oxidized_service::{Mutation, Query};
 use rocket::{
     fairing::{self, Fairing},
     Build, Rocket,
 };
 use sea_orm_rocket::Database;
 
-pub struct MiscTasksService {}
+// Every scrape is kept for a week, then one averaged sample per day until it expires
+const SWARM_HISTORY_SAMPLES_DAYS: i64 = 7;
+const SWARM_HISTORY_RETENTION_DAYS: i64 = 90;
+
+pub struct MiscTasksService {
+    supervisor: Supervisor,
+}
 
 #[rocket::async_trait]
 impl Fairing for MiscTasksService {
@@ -21,48 +29,157 @@ impl Fairing for MiscTasksService {
 
     async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
         let conn = &Db::fetch(&rocket).unwrap().conn;
-        let config = get_config();
 
-        self.spawn_vacuum(conn.clone());
-
-        if config.app.clean {
-            self.spawn_stale(conn.clone());
-        }
+        self.start(conn.clone());
 
         Ok(rocket)
     }
 }
 impl MiscTasksService {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(supervisor: Supervisor) -> Self {
+        Self { supervisor }
+    }
+
+    pub fn start(&self, conn: DatabaseConnection) {
+        let config = get_config();
+
+        self.spawn_vacuum(conn.clone());
+        self.spawn_release_backfill(conn.clone());
+        self.spawn_swarm_history_compaction(conn.clone());
+        self.spawn_tracker_queue_count(conn.clone());
+
+        if config.app.clean {
+            self.spawn_stale(conn);
+        }
     }
 
     pub fn spawn_vacuum(&self, conn: DatabaseConnection) {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
+        self.supervisor.spawn("vacuum", move |mut shutdown| {
+            let conn = conn.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
 
-        tokio::spawn(async move {
-            loop {
-                interval.tick().await;
+                loop {
+                    tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => return Ok(()),
+                        _ = interval.tick() => {}
+                    }
 
-                let vacuum = conn.execute_unprepared("VACUUM FULL").await;
+                    let vacuum = conn.execute_unprepared("VACUUM FULL").await;
 
-                if let Err(e) = vacuum {
-                    error!("Error vacuuming: {:?}", e);
+                    if let Err(e) = vacuum {
+                        error!("Error vacuuming: {:?}", e);
+                    }
                 }
             }
         });
     }
 
     pub fn spawn_stale(&self, conn: DatabaseConnection) {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
+        self.supervisor.spawn("stale", move |mut shutdown| {
+            let conn = conn.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
 
-        tokio::spawn(async move {
-            loop {
-                interval.tick().await;
+                loop {
+                    tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => return Ok(()),
+                        _ = interval.tick() => {}
+                    }
+
+                    Mutation::mark_stale(&conn)
+                        .await
+                        .context("Cannot mark stale torrents")?;
+                    // Wait for the next tracker scrape to do this for us
+                    // Mutation::delete_stale(&conn).await.unwrap();
+                }
+            }
+        });
+    }
+
+    pub fn spawn_swarm_history_compaction(&self, conn: DatabaseConnection) {
+        self.supervisor.spawn("swarm_history", move |mut shutdown| {
+            let conn = conn.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
+
+                loop {
+                    tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => return Ok(()),
+                        _ = interval.tick() => {}
+                    }
+
+                    let compact = Mutation::compact_swarm_history(
+                        &conn,
+                        SWARM_HISTORY_SAMPLES_DAYS,
+                        SWARM_HISTORY_RETENTION_DAYS,
+                    )
+                    .await;
+
+                    if let Err(e) = compact {
+                        error!("Error compacting swarm history: {:?}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn spawn_tracker_queue_count(&self, conn: DatabaseConnection) {
+        self.supervisor
+            .spawn("tracker_queue_count", move |mut shutdown| {
+                let conn = conn.clone();
+
+                async move {
+                    let mut interval =
+                        tokio::time::interval(tokio::time::Duration::from_secs(60 * 60));
+
+                    loop {
+                        tokio::select! {
+                            biased;
+                            _ = stopped(&mut shutdown) => return Ok(()),
+                            _ = interval.tick() => {}
+                        }
+
+                        let count = Mutation::recount_tracker_queue(&conn).await;
+
+                        if let Err(e) = count {
+                            error!("Error counting the tracker queue: {:?}", e);
+                        }
+                    }
+                }
+            });
+    }
+
+    // Parses the names of torrents indexed before releases were stored, a batch at a time.
+    // Stops for good once every torrent has one.
+    pub fn spawn_release_backfill(&self, conn: DatabaseConnection) {
+        self.supervisor.spawn("release_backfill", move |shutdown| {
+            let conn = conn.clone();
+
+            async move {
+                while !*shutdown.borrow() {
+                    let torrents = Query::find_torrents_without_release(&conn, 500)
+                        .await
+                        .context("Cannot find torrents without release")?;
+
+                    if torrents.is_empty() {
+                        break;
+                    }
+
+                    for (id, name) in torrents {
+                        Mutation::update_torrent_release(&conn, id, &name)
+                            .await
+                            .with_context(|| format!("Cannot update release of torrent {}", id))?;
+                    }
+                }
 
-                Mutation::mark_stale(&conn).await.unwrap();
-                // Wait for the next tracker scrape to do this for us
-                // Mutation::delete_stale(&conn).await.unwrap();
+                Ok(())
             }
         });
     }
@@ -1,3 +1,4 @@
 pub mod migrations;
 pub mod misc_tasks;
+pub mod supervisor;
 pub mod torrents;
@@ -0,0 +1,392 @@
+use chrono::{NaiveDateTime, Utc};
+use metrics::counter;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+// A task that ran this long before failing starts over from the shortest backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+// How long shutdown waits for tasks to finish what they are doing
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+// Idle workers still heartbeat this often, a worker silent for HEARTBEAT_TIMEOUT is stuck
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    Restarting,
+    Stopped,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TaskHealth {
+    pub state: TaskState,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<NaiveDateTime>,
+    // (re)starting counts as a heartbeat
+    pub last_heartbeat: Option<NaiveDateTime>,
+    // set by the task when it is running but cannot do its job
+    pub degraded: Option<String>,
+}
+
+// Runs the background tasks, restarting the ones that fail or panic with an exponential
+// backoff, and stops them all on shutdown
+#[derive(Clone)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<&'static str, TaskHealth>>>,
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(BTreeMap::new())),
+            handles: Arc::new(Mutex::new(vec![])),
+            shutdown: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    // `task` is called again for every restart. It gets a receiver that turns true on
+    // shutdown, and returning Ok means it is done for good. A task waiting to be restarted
+    // when shutdown starts is run once more, and should return as soon as it sees it.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: Fn(watch::Receiver<bool>) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        let shutdown = self.shutdown.subscribe();
+
+        let handle = tokio::spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+
+            loop {
+                supervisor.set_state(name, TaskState::Running);
+
+                let started = Instant::now();
+
+                let error = match tokio::spawn(task(shutdown.clone())).await {
+                    Ok(Ok(())) => break,
+                    Ok(Err(e)) => format!("{:#}", e),
+                    Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                    Err(e) => e.to_string(),
+                };
+
+                if *shutdown.borrow() {
+                    break;
+                }
+
+                if started.elapsed() >= HEALTHY_RUN {
+                    backoff = MIN_BACKOFF;
+                }
+
+                error!(
+                    "Task {} failed, restarting in {:?}: {}",
+                    name, backoff, error
+                );
+                counter!("oxidized_task_restarts_total", "task" => name).increment(1);
+
+                supervisor.record_failure(name, error);
+
+                let mut waiting = shutdown.clone();
+
+                // a shutdown cuts the wait short, the last run lets the task clean up
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = stopped(&mut waiting) => {}
+                }
+
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+
+            supervisor.set_state(name, TaskState::Stopped);
+        });
+
+        self.handles.lock().unwrap().push(handle);
+    }
+
+    // For work outside the supervisor that should stop along with it
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    // Tells the supervisor the task is making progress
+    pub fn heartbeat(&self, name: &'static str) {
+        self.beat(name, None);
+    }
+
+    // A heartbeat from a task that is alive but cannot do its job, until the next heartbeat
+    pub fn degraded(&self, name: &'static str, reason: String) {
+        self.beat(name, Some(reason));
+    }
+
+    pub fn health(&self) -> BTreeMap<&'static str, TaskHealth> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    // Asks every task to stop and waits for them, up to a grace period
+    pub async fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap());
+
+        if handles.is_empty() {
+            return;
+        }
+
+        info!("Waiting for {} background tasks to stop", handles.len());
+
+        let all = futures::future::join_all(handles);
+
+        if tokio::time::timeout(SHUTDOWN_GRACE, all).await.is_err() {
+            warn!(
+                "Background tasks did not stop within {:?}: {:?}",
+                SHUTDOWN_GRACE,
+                self.health()
+                    .into_iter()
+                    .filter(|(_, health)| health.state != TaskState::Stopped)
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    fn set_state(&self, name: &'static str, state: TaskState) {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        let health = tasks.entry(name).or_insert(TaskHealth {
+            state,
+            restarts: 0,
+            last_error: None,
+            last_error_at: None,
+            last_heartbeat: None,
+            degraded: None,
+        });
+
+        health.state = state;
+
+        if state == TaskState::Running {
+            health.last_heartbeat = Some(Utc::now().naive_utc());
+            health.degraded = None;
+        }
+    }
+
+    fn beat(&self, name: &'static str, degraded: Option<String>) {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        if let Some(health) = tasks.get_mut(name) {
+            health.last_heartbeat = Some(Utc::now().naive_utc());
+            health.degraded = degraded;
+        }
+    }
+
+    fn record_failure(&self, name: &'static str, error: String) {
+        let mut tasks = self.tasks.lock().unwrap();
+
+        if let Some(health) = tasks.get_mut(name) {
+            health.state = TaskState::Restarting;
+            health.restarts += 1;
+            health.last_error = Some(error);
+            health.last_error_at = Some(Utc::now().naive_utc());
+        }
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Resolves once shutdown has been requested
+pub async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => format!("panicked: {}", message),
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => format!("panicked: {}", message),
+            Err(_) => "panicked".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // start times of every run of a task
+    type Runs = Arc<Mutex<Vec<Instant>>>;
+
+    fn record(runs: &Runs) -> usize {
+        let mut runs = runs.lock().unwrap();
+        runs.push(Instant::now());
+        runs.len()
+    }
+
+    fn gaps(runs: &Runs) -> Vec<u64> {
+        runs.lock()
+            .unwrap()
+            .windows(2)
+            .map(|run| (run[1] - run[0]).as_secs())
+            .collect()
+    }
+
+    async fn wait_for_state(supervisor: &Supervisor, name: &'static str, state: TaskState) {
+        while supervisor.health().get(name).map(|health| health.state) != Some(state) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_failing_tasks_with_backoff() {
+        let supervisor = Supervisor::new();
+        let runs = Runs::default();
+
+        let task_runs = runs.clone();
+        supervisor.spawn("failing", move |_| {
+            let run = record(&task_runs);
+
+            async move {
+                match run {
+                    1..=3 => anyhow::bail!("run {} failed", run),
+                    // a healthy run resets the backoff
+                    4 => {
+                        tokio::time::sleep(HEALTHY_RUN).await;
+                        anyhow::bail!("run {} failed", run)
+                    }
+                    5 => anyhow::bail!("run {} failed", run),
+                    _ => Ok(()),
+                }
+            }
+        });
+
+        wait_for_state(&supervisor, "failing", TaskState::Stopped).await;
+
+        assert_eq!(gaps(&runs), [1, 2, 4, 61, 2]);
+
+        let health = &supervisor.health()["failing"];
+
+        assert_eq!(health.restarts, 5);
+        assert_eq!(health.last_error.as_deref(), Some("run 5 failed"));
+        assert!(health.last_error_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caps_backoff() {
+        let supervisor = Supervisor::new();
+        let runs = Runs::default();
+
+        let task_runs = runs.clone();
+        supervisor.spawn("failing", move |_| {
+            let run = record(&task_runs);
+
+            async move {
+                match run {
+                    1..=11 => anyhow::bail!("failed"),
+                    _ => Ok(()),
+                }
+            }
+        });
+
+        wait_for_state(&supervisor, "failing", TaskState::Stopped).await;
+
+        assert_eq!(gaps(&runs), [1, 2, 4, 8, 16, 32, 64, 128, 256, 300, 300]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_panicking_tasks() {
+        let supervisor = Supervisor::new();
+        let runs = Runs::default();
+
+        let task_runs = runs.clone();
+        supervisor.spawn("panicking", move |_| {
+            let run = record(&task_runs);
+
+            async move {
+                match run {
+                    1 => panic!("static message"),
+                    2 => panic!("run {}", run),
+                    _ => Ok(()),
+                }
+            }
+        });
+
+        wait_for_state(&supervisor, "panicking", TaskState::Stopped).await;
+
+        let health = &supervisor.health()["panicking"];
+
+        assert_eq!(gaps(&runs), [1, 2]);
+        assert_eq!(health.restarts, 2);
+        assert_eq!(health.last_error.as_deref(), Some("panicked: run 2"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_tasks_on_shutdown() {
+        let supervisor = Supervisor::new();
+        let runs = Runs::default();
+
+        supervisor.spawn("worker", |mut shutdown| async move {
+            stopped(&mut shutdown).await;
+            Ok(())
+        });
+
+        // waiting out its backoff when shutdown starts
+        let task_runs = runs.clone();
+        supervisor.spawn("failing", move |shutdown| {
+            record(&task_runs);
+
+            async move {
+                match *shutdown.borrow() {
+                    true => Ok(()),
+                    false => anyhow::bail!("failed"),
+                }
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        let started = Instant::now();
+        supervisor.shutdown().await;
+
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(runs.lock().unwrap().len(), 3);
+
+        for (name, health) in supervisor.health() {
+            assert_eq!(health.state, TaskState::Stopped, "{}", name);
+        }
+
+        assert_eq!(supervisor.health()["failing"].restarts, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_tasks_that_ignore_shutdown() {
+        let supervisor = Supervisor::new();
+
+        supervisor.spawn("stuck", |_| async {
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+
+        tokio::task::yield_now().await;
+
+        let started = Instant::now();
+        supervisor.shutdown().await;
+
+        assert_eq!(started.elapsed(), SHUTDOWN_GRACE);
+        assert_eq!(supervisor.health()["stuck"].state, TaskState::Running);
+    }
+}
@@ -1,21 +1,43 @@
 use crate::pool::Db;
+use crate::service::supervisor::{stopped, Supervisor, HEARTBEAT_INTERVAL};
+use anyhow::Context;
+use metrics::{counter, gauge};
 use oxidized_config::{get_config, Settings};
-use oxidized_entity::{sea_orm::DatabaseConnection, torrent, torrent::Tracker};
+use oxidized_entity::{
+    info_hash::InfoHash,
+    sea_orm::DatabaseConnection,
+    torrent,
+    torrent::{File, Tracker},
+};
 use oxidized_service::{Mutation, Query};
 use oxidized_torrent::info::TorrentInfo;
 use oxidized_torrent::nsfw_filter::NSFWFilter;
 use oxidized_torrent::spider::{MagneticoDTorrent, Spider};
+use oxidized_torrent::torrent_file;
 use oxidized_torrent::trackers::TorrentTrackers;
 use rocket::fairing::{self, Fairing};
 use rocket::{Build, Rocket};
 use sea_orm_rocket::Database;
 use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Receiver};
 use tokio::sync::Mutex;
+use tokio::task::{JoinError, JoinSet};
+
+const QUEUE_BATCH_SIZE: u64 = 50;
+const TRACKERS_CHUNK_SIZE: usize = 10;
+// Chunks of claimed torrents waiting for a scrape
+const TRACKERS_CHANNEL_SIZE: usize = 10;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Receivers outlive the task reading them, so a restarted consumer picks up where it left off
+type SharedReceiver<T> = Arc<Mutex<Receiver<T>>>;
 
 pub struct TorrentService {
-    queue: Arc<Mutex<HashSet<i32>>>,
+    supervisor: Supervisor,
+    spider: bool,
+    workers: bool,
 }
 
 #[rocket::async_trait]
@@ -29,195 +51,339 @@ impl Fairing for TorrentService {
 
     async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
         let conn = &Db::fetch(&rocket).unwrap().conn;
-        let config = get_config();
 
-        let (info_rx, trackers_rx) = self.spawn_producer(conn.clone(), config.clone());
+        self.start(conn.clone()).await;
 
-        if config.app.update_info {
-            self.spawn_consumer_info(conn.clone(), info_rx);
+        Ok(rocket)
+    }
+}
+impl TorrentService {
+    pub fn new(supervisor: Supervisor) -> Self {
+        Self {
+            supervisor,
+            spider: true,
+            workers: true,
         }
+    }
 
-        if config.app.update_trackers {
-            self.spawn_consumer_trackers(conn.clone(), trackers_rx);
-        }
+    // The DHT spider and its consumers
+    pub fn with_spider(mut self, enabled: bool) -> Self {
+        self.spider = enabled;
+        self
+    }
+
+    // The info and tracker consumers working through the queue
+    pub fn with_workers(mut self, enabled: bool) -> Self {
+        self.workers = enabled;
+        self
+    }
+
+    // Spawns whatever is enabled both here and in the config
+    pub async fn start(&self, conn: DatabaseConnection) {
+        let config = get_config();
 
-        if config.app.spider {
-            let spider = Spider::new();
+        if self.workers {
+            let (info_rx, trackers_rx) = self.spawn_producer(conn.clone(), config.clone());
 
-            let spider_rx = spider.start().await;
+            if config.app.update_info {
+                self.spawn_consumer_info(conn.clone(), info_rx);
+            }
 
-            let _ = self.spawn_consumer_spider(conn.clone(), spider_rx).await;
+            if config.app.update_trackers {
+                self.spawn_consumer_trackers(conn.clone(), trackers_rx);
+            }
         }
 
-        Ok(rocket)
-    }
-}
-impl TorrentService {
-    pub async fn new() -> Self {
-        let queue: Arc<Mutex<HashSet<i32>>> = Arc::new(Mutex::new(HashSet::new()));
+        if self.spider && config.app.spider {
+            let spider = Spider::new()
+                .with_sampling(config.app.sample_infohashes)
+                .with_shutdown(self.supervisor.subscribe());
 
-        Self { queue }
+            let (spider_rx, samples_rx) = spider.start().await;
+
+            self.spawn_consumer_spider(conn.clone(), spider_rx);
+            self.spawn_consumer_samples(conn, samples_rx);
+        }
     }
 
+    // Claims work from the queues in the database, so several workers can share them. Only
+    // what fits in the channels is claimed, the rest is left for workers that keep up.
     pub fn spawn_producer(
         &self,
         conn: DatabaseConnection,
         config: Settings,
-    ) -> (
-        UnboundedReceiver<torrent::Model>,
-        UnboundedReceiver<Vec<torrent::Model>>,
-    ) {
-        let queue = self.queue.clone();
-        let (info_tx, info_rx) = unbounded_channel::<torrent::Model>();
-        let (trackers_tx, trackers_rx) = unbounded_channel::<Vec<torrent::Model>>();
+    ) -> (Receiver<torrent::Model>, Receiver<Vec<torrent::Model>>) {
+        // room for a batch while the fetches in flight finish
+        let info_channel_size = (QUEUE_BATCH_SIZE as usize).max(2 * config.app.info_concurrency);
+
+        let (info_tx, info_rx) = channel::<torrent::Model>(info_channel_size);
+        let (trackers_tx, trackers_rx) = channel::<Vec<torrent::Model>>(TRACKERS_CHANNEL_SIZE);
+
+        let supervisor = self.supervisor.clone();
+
+        self.supervisor.spawn("producer", move |mut shutdown| {
+            let supervisor = supervisor.clone();
+            let conn = conn.clone();
+            let config = config.clone();
+            let info_tx = info_tx.clone();
+            let trackers_tx = trackers_tx.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3));
+
+                loop {
+                    tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => return Ok(()),
+                        _ = interval.tick() => {}
+                    }
 
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3));
+                    supervisor.heartbeat("producer");
 
-            loop {
-                interval.tick().await;
-                let mut queue_lock = queue.lock().await;
+                    // the producer is the only sender, so what is claimed is sent without waiting
+                    let info_limit = QUEUE_BATCH_SIZE.min(info_tx.capacity() as u64);
 
-                if config.app.update_info {
-                    let torrents = Query::find_torrent_queue_info(
-                        &conn,
-                        Some(queue_lock.iter().map(|x| *x).collect()),
-                    )
-                    .await
-                    .expect("Cannot find torrents in queue");
+                    if config.app.update_info && info_limit > 0 {
+                        let torrents = Mutation::claim_torrent_queue_info(&conn, info_limit)
+                            .await
+                            .context("Cannot claim torrents from the info queue")?;
 
-                    for torrent in &torrents {
-                        let id = torrent.id.clone();
+                        for torrent in torrents {
+                            info_tx.send(torrent).await?;
+                        }
+                    }
+
+                    let trackers_limit =
+                        QUEUE_BATCH_SIZE.min((trackers_tx.capacity() * TRACKERS_CHUNK_SIZE) as u64);
 
-                        info_tx.send(torrent.clone()).unwrap();
+                    if config.app.update_trackers && trackers_limit > 0 {
+                        let torrents_trackers =
+                            Mutation::claim_torrent_queue_trackers(&conn, trackers_limit)
+                                .await
+                                .context("Cannot claim torrents from the tracker queue")?;
 
-                        queue_lock.insert(id);
+                        for chunk in torrents_trackers.chunks(TRACKERS_CHUNK_SIZE) {
+                            trackers_tx.send(chunk.to_vec()).await?;
+                        }
                     }
                 }
+            }
+        });
+
+        (info_rx, trackers_rx)
+    }
+
+    // Runs until the spider closes its channel, which it does on shutdown
+    pub fn spawn_consumer_spider(&self, conn: DatabaseConnection, rx: Receiver<MagneticoDTorrent>) {
+        let config = get_config();
+        let rx: SharedReceiver<MagneticoDTorrent> = Arc::new(Mutex::new(rx));
+
+        let supervisor = self.supervisor.clone();
+
+        self.supervisor.spawn("spider", move |_| {
+            let supervisor = supervisor.clone();
+            let conn = conn.clone();
+            let config = config.clone();
+            let rx = rx.clone();
+
+            async move {
+                let mut rx = rx.lock().await;
+                let mut filter = NSFWFilter::new().await;
+
+                loop {
+                    supervisor.heartbeat("spider");
+                    gauge!("oxidized_channel_depth", "channel" => "torrents").set(rx.len() as f64);
 
-                if config.app.update_trackers {
-                    let torrents_trackers = Query::find_torrent_queue_trackers(
+                    let torrent = tokio::select! {
+                        torrent = rx.recv() => torrent,
+                        _ = tokio::time::sleep(HEARTBEAT_INTERVAL) => continue,
+                    };
+
+                    let Some(torrent) = torrent else {
+                        break;
+                    };
+
+                    counter!("oxidized_spider_torrents_received_total").increment(1);
+
+                    let exists = Query::exists_torrent_by_info_hash(&conn, &torrent.info_hash)
+                        .await
+                        .unwrap_or(false);
+
+                    if exists {
+                        counter!("oxidized_spider_torrents_total", "outcome" => "duplicate")
+                            .increment(1);
+                        continue;
+                    }
+
+                    if config.app.filter_nsfw && filter.test(&torrent.name).await {
+                        counter!("oxidized_spider_torrents_total", "outcome" => "filtered")
+                            .increment(1);
+                        continue;
+                    }
+
+                    let size = torrent.files.iter().map(|f| f.size).sum();
+
+                    let created = Mutation::create_torrent_internal(
                         &conn,
-                        Some(queue_lock.iter().map(|x| *x).collect()),
+                        torrent.info_hash.clone().to_uppercase(),
+                        torrent.name.clone(),
+                        size,
+                        torrent
+                            .files
+                            .iter()
+                            .map(|f| File::new(f.path.clone(), f.size))
+                            .collect::<Vec<File>>(),
                     )
-                    .await
-                    .expect("Cannot find tracker torrents in queue");
+                    .await;
 
-                    for chunk in torrents_trackers.chunks(10) {
-                        trackers_tx.send(chunk.to_vec()).unwrap();
+                    let outcome = if created.is_ok() { "added" } else { "failed" };
 
-                        for torrent in chunk {
-                            let id = torrent.id.clone();
+                    counter!("oxidized_spider_torrents_total", "outcome" => outcome).increment(1);
 
-                            queue_lock.insert(id);
+                    if let Ok(created) = created {
+                        let id = created.id.unwrap();
+
+                        if let Err(e) =
+                            Mutation::save_torrent_metadata(&conn, id, torrent.info).await
+                        {
+                            error!("Cannot save metadata for torrent {}: {:?}", id, e);
                         }
                     }
                 }
+
+                Ok(())
             }
         });
-
-        (info_rx, trackers_rx)
     }
 
-    pub async fn spawn_consumer_spider(
-        &self,
-        conn: DatabaseConnection,
-        mut rx: UnboundedReceiver<MagneticoDTorrent>,
-    ) {
-        let config = get_config();
-        let mut filter = NSFWFilter::new().await;
+    pub fn spawn_consumer_samples(&self, conn: DatabaseConnection, rx: Receiver<Vec<String>>) {
+        let rx: SharedReceiver<Vec<String>> = Arc::new(Mutex::new(rx));
+
+        let supervisor = self.supervisor.clone();
+
+        self.supervisor.spawn("samples", move |_| {
+            let supervisor = supervisor.clone();
+            let conn = conn.clone();
+            let rx = rx.clone();
+
+            async move {
+                let mut rx = rx.lock().await;
+
+                loop {
+                    supervisor.heartbeat("samples");
+                    gauge!("oxidized_channel_depth", "channel" => "samples").set(rx.len() as f64);
+
+                    let info_hashes = tokio::select! {
+                        info_hashes = rx.recv() => info_hashes,
+                        _ = tokio::time::sleep(HEARTBEAT_INTERVAL) => continue,
+                    };
+
+                    let Some(info_hashes) = info_hashes else {
+                        break;
+                    };
 
-        tokio::spawn(async move {
-            while let Some(torrent) = rx.recv().await {
-                let exists = Query::exists_torrent_by_info_hash(&conn, &torrent.info_hash)
+                    let info_hashes = info_hashes
+                        .iter()
+                        .map(|info_hash| info_hash.to_uppercase())
+                        .collect::<HashSet<String>>();
+
+                    let info_hashes_len = info_hashes.len();
+
+                    let existing = Query::find_existing_info_hashes(
+                        &conn,
+                        info_hashes.iter().cloned().collect(),
+                    )
                     .await
-                    .unwrap_or(false);
+                    .unwrap_or_default();
 
-                if exists {
-                    continue;
-                }
+                    let new = info_hashes
+                        .into_iter()
+                        .filter(|info_hash| !existing.contains(info_hash))
+                        .collect::<Vec<String>>();
+
+                    counter!("oxidized_spider_samples_total", "outcome" => "known")
+                        .increment((info_hashes_len - new.len()) as u64);
+                    counter!("oxidized_spider_samples_total", "outcome" => "new")
+                        .increment(new.len() as u64);
 
-                if config.app.filter_nsfw {
-                    if filter.test(&torrent.name).await {
+                    if new.is_empty() {
                         continue;
                     }
+
+                    if let Err(e) = Mutation::create_torrents(&conn, new).await {
+                        error!("Cannot add sampled torrents: {:?}", e);
+                    }
                 }
 
-                let size = torrent
-                    .files
-                    .iter()
-                    .map(|f| (f.size / 1000000) as i32)
-                    .collect::<Vec<i32>>()
-                    .iter()
-                    .sum();
-
-                let _ = Mutation::create_torrent_internal(
-                    &conn,
-                    torrent.info_hash.clone().to_uppercase(),
-                    torrent.name.clone(),
-                    size,
-                    torrent
-                        .files
-                        .iter()
-                        .map(|f| f.path.clone())
-                        .collect::<Vec<String>>(),
-                )
-                .await;
+                Ok(())
             }
         });
     }
 
-    pub fn spawn_consumer_info(
-        &self,
-        conn: DatabaseConnection,
-        mut info_rx: UnboundedReceiver<torrent::Model>,
-    ) {
-        let queue = self.queue.clone();
+    // Fetches metadata for up to `app.info_concurrency` torrents at once, over one session
+    pub fn spawn_consumer_info(&self, conn: DatabaseConnection, info_rx: Receiver<torrent::Model>) {
+        let config = get_config();
+        let concurrency = config.app.info_concurrency.max(1);
+        let info_rx: SharedReceiver<torrent::Model> = Arc::new(Mutex::new(info_rx));
 
-        tokio::spawn(async move {
-            let torrent_info = TorrentInfo::new().await.unwrap();
+        let supervisor = self.supervisor.clone();
 
-            while let Some(torrent) = info_rx.recv().await {
-                let info = torrent_info.get_torrent_info(&torrent.info_hash).await;
+        self.supervisor.spawn("info", move |mut shutdown| {
+            let supervisor = supervisor.clone();
+            let conn = conn.clone();
+            let config = config.clone();
+            let info_rx = info_rx.clone();
 
-                if let Ok(info) = info {
-                    let mut size = 0;
-                    let mut files = vec![];
+            async move {
+                let mut info_rx = info_rx.lock().await;
+                let release = |torrent: torrent::Model| vec![torrent.id];
 
-                    for (filename, length) in info.iter_filenames_and_lengths().unwrap() {
-                        let file_str = filename.to_string().unwrap();
+                if *shutdown.borrow() {
+                    return release_queued(&conn, &mut info_rx, release).await;
+                }
 
-                        files.push(file_str);
+                let torrent_info = TorrentInfo::new()
+                    .await?
+                    .with_timeout(Duration::from_secs(config.app.info_timeout));
 
-                        size += (length / 1000000) as i32;
-                    }
+                let fetcher = Arc::new(InfoFetcher {
+                    conn: conn.clone(),
+                    torrent_info,
+                    retry_delay: Duration::from_secs(config.app.info_retry_delay),
+                    max_attempts: config.app.info_max_attempts,
+                });
 
-                    let name = match info.name {
-                        Some(name) => name.to_string(),
-                        None => "".to_string(),
-                    };
+                let mut running = JoinSet::new();
 
-                    let _ = Mutation::update_torrent_info(&conn, torrent.id, name, size, files)
-                        .await
-                        .expect("Cannot update torrent info");
-                } else {
-                    println!("Cannot get info for torrent: {}", torrent.info_hash);
-                    println!("{:?}", info);
-
-                    // 30 days or never scraped
-                    if torrent.last_scrape.is_none()
-                        || torrent.last_scrape.unwrap().and_utc()
-                            < (chrono::Utc::now() - chrono::Duration::try_days(30).unwrap())
-                    {
-                        let _ = Mutation::delete_torrent(&conn, torrent.id)
-                            .await
-                            .expect("Cannot delete torrent");
-                    }
-                }
+                loop {
+                    supervisor.heartbeat("info");
+                    gauge!("oxidized_channel_depth", "channel" => "info").set(info_rx.len() as f64);
+                    gauge!("oxidized_info_fetches_in_flight").set(running.len() as f64);
+
+                    // the torrents being fetched are finished, the ones still queued are not
+                    tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => {
+                            finish_running(&mut running).await;
+
+                            return release_queued(&conn, &mut info_rx, release).await;
+                        }
+                        // a failed fetch only affects its torrent, whose lease runs out
+                        Some(finished) = running.join_next() => log_fetch_error(finished),
+                        torrent = info_rx.recv(), if running.len() < concurrency => {
+                            let Some(torrent) = torrent else {
+                                finish_running(&mut running).await;
 
-                let mut queue_lock = queue.lock().await;
+                                return Ok(());
+                            };
 
-                queue_lock.remove(&torrent.id);
+                            if !lease_expired(&torrent) {
+                                running.spawn(fetcher.clone().fetch(torrent));
+                            }
+                        }
+                        _ = tokio::time::sleep(HEARTBEAT_INTERVAL) => {}
+                    }
+                }
             }
         });
     }
@@ -225,52 +391,99 @@ impl TorrentService {
     pub fn spawn_consumer_trackers(
         &self,
         conn: DatabaseConnection,
-        mut trackers_rx: UnboundedReceiver<Vec<torrent::Model>>,
+        trackers_rx: Receiver<Vec<torrent::Model>>,
     ) {
-        let queue = self.queue.clone();
         let config = get_config();
+        let trackers_rx: SharedReceiver<Vec<torrent::Model>> = Arc::new(Mutex::new(trackers_rx));
 
-        tokio::spawn(async move {
-            let mut torrent_tracking = TorrentTrackers::new().await.unwrap();
+        let supervisor = self.supervisor.clone();
 
-            while let Some(torrents_chunk) = trackers_rx.recv().await {
-                let conn_consumer_trackers = conn.clone();
-                let queue_consumer_trackers = queue.clone();
+        self.supervisor.spawn("trackers", move |mut shutdown| {
+            let supervisor = supervisor.clone();
+            let conn = conn.clone();
+            let config = config.clone();
+            let trackers_rx = trackers_rx.clone();
 
-                // let start = std::time::Instant::now();
-                let tracker_info = torrent_tracking
-                    .get_torrent_trackers(
-                        torrents_chunk
-                            .iter()
-                            .map(|torrent| torrent.info_hash.clone())
-                            .collect(),
-                    )
-                    .await;
+            async move {
+                let mut trackers_rx = trackers_rx.lock().await;
+                let release = |chunk: Vec<torrent::Model>| -> Vec<i32> {
+                    chunk.iter().map(|torrent| torrent.id).collect()
+                };
 
-                if let Ok(tracker_info) = tracker_info {
-                    // println!(
-                    //     "Scrape took: {}ms with {} trackers",
-                    //     start.elapsed().as_millis(),
-                    //     tracker_info.len()
-                    // );
+                if *shutdown.borrow() {
+                    return release_queued(&conn, &mut trackers_rx, release).await;
+                }
 
-                    if tracker_info.is_empty() {
+                let mut torrent_tracking = TorrentTrackers::new().await?;
+
+                loop {
+                    match torrent_tracking.tracker_count() {
+                        0 => supervisor.degraded("trackers", "Tracker list is empty".to_string()),
+                        _ => supervisor.heartbeat("trackers"),
+                    }
+
+                    gauge!("oxidized_channel_depth", "channel" => "trackers")
+                        .set(trackers_rx.len() as f64);
+
+                    let torrents_chunk = tokio::select! {
+                        biased;
+                        _ = stopped(&mut shutdown) => {
+                            return release_queued(&conn, &mut trackers_rx, release).await;
+                        }
+                        torrents_chunk = trackers_rx.recv() => torrents_chunk,
+                        _ = tokio::time::sleep(HEARTBEAT_INTERVAL) => continue,
+                    };
+
+                    let Some(torrents_chunk) = torrents_chunk else {
+                        return Ok(());
+                    };
+
+                    let torrents_chunk: Vec<torrent::Model> = torrents_chunk
+                        .into_iter()
+                        .filter(|torrent| !lease_expired(torrent))
+                        .collect();
+
+                    if torrents_chunk.is_empty() {
                         continue;
                     }
 
-                    for torrent in torrents_chunk {
-                        let mut trackers: Vec<Tracker> = vec![];
+                    // trackers know v2 torrents by their truncated hash
+                    let swarm_hashes: Vec<Option<String>> = torrents_chunk
+                        .iter()
+                        .map(|torrent| {
+                            InfoHash::parse(&torrent.info_hash)
+                                .map(|info_hash| InfoHash::V1(info_hash.truncated()).to_string())
+                                .ok()
+                        })
+                        .collect();
+
+                    let tracker_info = torrent_tracking
+                        .get_torrent_trackers(swarm_hashes.iter().flatten().cloned().collect())
+                        .await;
+
+                    let Ok(tracker_info) = tracker_info else {
+                        continue;
+                    };
 
-                        let tracker_info = &tracker_info;
+                    if tracker_info.is_empty() {
+                        continue;
+                    }
 
-                        for (tracker, scrape) in tracker_info {
-                            let stats = scrape.stats.get(torrent.info_hash.to_uppercase().as_str());
+                    for (torrent, swarm_hash) in torrents_chunk.into_iter().zip(swarm_hashes) {
+                        let Some(swarm_hash) = swarm_hash else {
+                            warn!(
+                                "Cannot scrape torrent {} with invalid info hash",
+                                torrent.id
+                            );
+                            continue;
+                        };
 
-                            if stats.is_none() {
-                                continue;
-                            }
+                        let mut trackers: Vec<Tracker> = vec![];
 
-                            let stats = stats.unwrap();
+                        for (tracker, scrape) in &tracker_info {
+                            let Some(stats) = scrape.stats.get(swarm_hash.as_str()) else {
+                                continue;
+                            };
 
                             trackers.push(Tracker {
                                 last_scrape: chrono::Utc::now().naive_utc(),
@@ -280,35 +493,202 @@ impl TorrentService {
                             })
                         }
 
-                        let new_torrent = Mutation::update_torrent_trackers(
-                            &conn_consumer_trackers,
-                            torrent.id,
-                            trackers,
-                        )
-                        .await
-                        .unwrap();
+                        // no tracker answered for it, the lease runs out and it is tried again
+                        if trackers.is_empty() {
+                            continue;
+                        }
+
+                        let new_torrent =
+                            Mutation::update_torrent_trackers(&conn, torrent.id, trackers)
+                                .await
+                                .context("Cannot update torrent trackers")?;
 
                         if config.app.clean {
                             if let Some(last_stale) = new_torrent.last_stale {
                                 if last_stale.and_utc()
                                     < (chrono::Utc::now() - chrono::Duration::try_days(3).unwrap())
                                 {
-                                    let _ = Mutation::delete_torrent(
-                                        &conn_consumer_trackers,
-                                        torrent.id,
-                                    )
-                                    .await
-                                    .expect("Cannot delete torrent");
+                                    Mutation::delete_torrent(&conn, torrent.id)
+                                        .await
+                                        .context("Cannot delete torrent")?;
                                 }
                             }
                         }
-
-                        let mut queue_lock = queue_consumer_trackers.lock().await;
-
-                        queue_lock.remove(&torrent.id);
                     }
                 }
             }
         });
     }
 }
+
+// Shared by the metadata fetches running at once
+struct InfoFetcher {
+    conn: DatabaseConnection,
+    torrent_info: TorrentInfo,
+    retry_delay: Duration,
+    max_attempts: u32,
+}
+
+impl InfoFetcher {
+    async fn fetch(self: Arc<Self>, torrent: torrent::Model) -> anyhow::Result<()> {
+        let attempt = torrent.metadata_attempts.max(0) as u32 + 1;
+
+        // librqbit checks the metadata against a SHA-1 info hash, so v2 torrents cannot resolve
+        // yet. They stay queued, out of the way, without using up their attempts.
+        let info_hash = match InfoHash::parse(&torrent.info_hash) {
+            Ok(info_hash @ InfoHash::V1(_)) => info_hash.to_string(),
+            Ok(InfoHash::V2(_)) => {
+                let next_attempt_at =
+                    chrono::Utc::now().naive_utc() + chrono::Duration::from_std(MAX_RETRY_DELAY)?;
+
+                return Mutation::fail_torrent_info(
+                    &self.conn,
+                    torrent.id,
+                    torrent.metadata_attempts,
+                    next_attempt_at,
+                    "v2 metadata fetch unsupported".to_string(),
+                )
+                .await
+                .context("Cannot record failed info fetch");
+            }
+            Err(e) => return self.give_up(torrent, attempt, e.into()).await,
+        };
+
+        let info = match self.torrent_info.get_torrent_info(&info_hash).await {
+            Ok(info) => info,
+            Err(e) => return self.failed(torrent, e).await,
+        };
+
+        let files = match torrent_file::files(&info) {
+            Ok(files) => files,
+            Err(e) => return self.failed(torrent, e.context("Cannot read files")).await,
+        };
+
+        let size = files.iter().map(|(_, length)| length).sum();
+        let files = files
+            .into_iter()
+            .map(|(path, length)| File::new(path, length))
+            .collect();
+
+        let encoded = torrent_file::encode_info(&torrent.info_hash, &info);
+
+        let name = match info.name {
+            Some(name) => name.to_string(),
+            None => "".to_string(),
+        };
+
+        Mutation::update_torrent_info(&self.conn, torrent.id, name, size, files)
+            .await
+            .context("Cannot update torrent info")?;
+
+        if let Some(encoded) = encoded {
+            if let Err(e) = Mutation::save_torrent_metadata(&self.conn, torrent.id, encoded).await {
+                error!("Cannot save metadata for torrent {}: {:?}", torrent.id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Backs the torrent off exponentially, and only gives up after `max_attempts`
+    async fn failed(&self, torrent: torrent::Model, error: anyhow::Error) -> anyhow::Result<()> {
+        let attempt = torrent.metadata_attempts.max(0) as u32 + 1;
+
+        if attempt < self.max_attempts {
+            let delay = self
+                .retry_delay
+                .saturating_mul(2u32.saturating_pow(attempt - 1))
+                .min(MAX_RETRY_DELAY);
+
+            info!(
+                "Cannot get info for torrent {} (attempt {}), retrying in {:?}: {:#}",
+                torrent.info_hash, attempt, delay, error
+            );
+
+            let next_attempt_at =
+                chrono::Utc::now().naive_utc() + chrono::Duration::from_std(delay)?;
+
+            return Mutation::fail_torrent_info(
+                &self.conn,
+                torrent.id,
+                attempt as i32,
+                next_attempt_at,
+                format!("{:#}", error),
+            )
+            .await
+            .context("Cannot record failed info fetch");
+        }
+
+        self.give_up(torrent, attempt, error).await
+    }
+
+    async fn give_up(
+        &self,
+        torrent: torrent::Model,
+        attempt: u32,
+        error: anyhow::Error,
+    ) -> anyhow::Result<()> {
+        warn!(
+            "Giving up on info for torrent {} after {} attempts: {:#}",
+            torrent.info_hash, attempt, error
+        );
+
+        // 30 days or never scraped
+        if torrent.last_scrape.is_none()
+            || torrent.last_scrape.unwrap().and_utc()
+                < (chrono::Utc::now() - chrono::Duration::try_days(30).unwrap())
+        {
+            Mutation::delete_torrent(&self.conn, torrent.id)
+                .await
+                .context("Cannot delete torrent")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Waits for the metadata fetches in flight, they time out on their own
+async fn finish_running(running: &mut JoinSet<anyhow::Result<()>>) {
+    while let Some(finished) = running.join_next().await {
+        log_fetch_error(finished);
+    }
+}
+
+fn log_fetch_error(finished: Result<anyhow::Result<()>, JoinError>) {
+    match finished {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Cannot finish metadata fetch: {:#}", e),
+        Err(e) => error!("Cannot finish metadata fetch: {}", e),
+    }
+}
+
+// Empties a consumer's channel on shutdown, handing the leases of what was still queued back
+// so other workers do not have to wait for them to run out
+async fn release_queued<T>(
+    conn: &DatabaseConnection,
+    rx: &mut Receiver<T>,
+    ids: impl Fn(T) -> Vec<i32>,
+) -> anyhow::Result<()> {
+    rx.close();
+
+    let mut queued = vec![];
+
+    while let Ok(item) = rx.try_recv() {
+        queued.extend(ids(item));
+    }
+
+    if !queued.is_empty() {
+        info!("Releasing {} queued torrents", queued.len());
+
+        Mutation::release_torrents(conn, queued).await?;
+    }
+
+    Ok(())
+}
+
+// A claim that sat in the channel past its lease may already belong to another worker
+fn lease_expired(torrent: &torrent::Model) -> bool {
+    torrent
+        .leased_until
+        .is_none_or(|leased_until| leased_until < chrono::Utc::now().naive_utc())
+}
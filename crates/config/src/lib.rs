@@ -1,4 +1,4 @@
-use config::{Config, Environment, File};
+use config::{Config, ConfigError, Environment, File};
 use serde::Serialize;
 use serde_derive::Deserialize;
 
@@ -17,7 +17,12 @@ pub struct DatabaseSettings {
 #[derive(Deserialize, Serialize, Clone)]
 pub struct AppSettings {
     pub spider: bool,
+    pub sample_infohashes: bool,
     pub update_info: bool,
+    pub info_concurrency: usize,
+    pub info_timeout: u64,
+    pub info_retry_delay: u64,
+    pub info_max_attempts: u32,
     pub update_trackers: bool,
     pub clean: bool,
     pub filter_nsfw: bool,
@@ -32,7 +37,11 @@ pub struct Settings {
 }
 
 pub fn get_config() -> Settings {
-    let settings = Config::builder()
+    try_get_config().unwrap()
+}
+
+pub fn try_get_config() -> Result<Settings, ConfigError> {
+    Config::builder()
         .add_source(File::with_name("default"))
         .add_source(File::with_name("config").required(false))
         .add_source(
@@ -40,8 +49,6 @@ pub fn get_config() -> Settings {
                 .separator("_")
                 .try_parsing(true),
         )
-        .build()
-        .unwrap();
-
-    settings.try_deserialize().unwrap()
+        .build()?
+        .try_deserialize()
 }
@@ -0,0 +1,186 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Multihash prefix of a BitTorrent v2 info hash: sha2-256 (0x12), 32 bytes long (0x20)
+const SHA256_MULTIHASH_PREFIX: &str = "1220";
+
+// A validated info hash. Stored and displayed as uppercase hex, 40 characters for v1 (SHA-1)
+// and 64 for v2 (SHA-256).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InfoHash {
+    V1([u8; 20]),
+    V2([u8; 32]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoHashError(String);
+
+impl fmt::Display for InfoHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InfoHashError {}
+
+impl InfoHash {
+    // Accepts a v1 hash as 40 hex or 32 base32 characters, a v2 hash as 64 hex characters or
+    // a "1220" multihash, or a magnet URI carrying either. Magnets for hybrid torrents list
+    // both, the v1 hash is preferred since that is what the DHT and trackers are asked about.
+    pub fn parse(input: &str) -> Result<Self, InfoHashError> {
+        let input = input.trim();
+
+        if let Some(query) = strip_prefix_ignore_case(input, "magnet:?") {
+            return InfoHash::parse_magnet(query);
+        }
+
+        InfoHash::parse_v1(input)
+            .or_else(|| InfoHash::parse_v2(input))
+            .ok_or_else(|| {
+                InfoHashError(
+                    "Info hash must be 40 hex or 32 base32 characters (v1), 64 hex characters \
+                     or a 1220 multihash (v2), or a magnet URI"
+                        .to_string(),
+                )
+            })
+    }
+
+    fn parse_magnet(query: &str) -> Result<Self, InfoHashError> {
+        let mut v2 = None;
+
+        for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            if !(key == "xt" || key.starts_with("xt.")) {
+                continue;
+            }
+
+            let value = value.replace("%3A", ":").replace("%3a", ":");
+
+            if let Some(hash) = strip_prefix_ignore_case(&value, "urn:btih:") {
+                return InfoHash::parse_v1(hash)
+                    .ok_or_else(|| InfoHashError(format!("Invalid btih in magnet URI: {}", hash)));
+            }
+
+            if let Some(hash) = strip_prefix_ignore_case(&value, "urn:btmh:") {
+                v2 = Some(
+                    strip_prefix_ignore_case(hash, SHA256_MULTIHASH_PREFIX)
+                        .and_then(decode_hex::<32>)
+                        .map(InfoHash::V2)
+                        .ok_or_else(|| {
+                            InfoHashError(format!("Invalid btmh in magnet URI: {}", hash))
+                        })?,
+                );
+            }
+        }
+
+        v2.ok_or_else(|| InfoHashError("Magnet URI has no btih or btmh topic".to_string()))
+    }
+
+    fn parse_v1(hash: &str) -> Option<Self> {
+        match hash.len() {
+            40 => decode_hex::<20>(hash).map(InfoHash::V1),
+            32 => decode_base32(hash).map(InfoHash::V1),
+            _ => None,
+        }
+    }
+
+    fn parse_v2(hash: &str) -> Option<Self> {
+        let hash = match hash.len() {
+            68 => strip_prefix_ignore_case(hash, SHA256_MULTIHASH_PREFIX)?,
+            _ => hash,
+        };
+
+        decode_hex::<32>(hash).map(InfoHash::V2)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            InfoHash::V1(bytes) => bytes,
+            InfoHash::V2(bytes) => bytes,
+        }
+    }
+
+    // The 20 bytes peers use for the torrent on the DHT and trackers, v2 hashes are truncated
+    pub fn truncated(&self) -> [u8; 20] {
+        let mut truncated = [0; 20];
+        truncated.copy_from_slice(&self.as_bytes()[..20]);
+
+        truncated
+    }
+
+    // The exact topic for a magnet URI, urn:btih for v1 and urn:btmh for v2
+    pub fn magnet_topic(&self) -> String {
+        match self {
+            InfoHash::V1(_) => format!("urn:btih:{}", self),
+            InfoHash::V2(_) => format!("urn:btmh:{}{}", SHA256_MULTIHASH_PREFIX, self),
+        }
+    }
+}
+
+impl FromStr for InfoHash {
+    type Err = InfoHashError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        InfoHash::parse(input)
+    }
+}
+
+impl fmt::Display for InfoHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.as_bytes() {
+            write!(f, "{:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    if value.len() >= prefix.len()
+        && value.is_char_boundary(prefix.len())
+        && value[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        return Some(&value[prefix.len()..]);
+    }
+
+    None
+}
+
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+// RFC 4648 base32 without padding, 32 characters make exactly 20 bytes
+fn decode_base32(base32: &str) -> Option<[u8; 20]> {
+    let mut bytes = [0; 20];
+    let (mut buffer, mut bits, mut i) = (0u64, 0, 0);
+
+    for c in base32.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes[i] = (buffer >> bits) as u8;
+            buffer &= (1 << bits) - 1;
+            i += 1;
+        }
+    }
+
+    Some(bytes)
+}
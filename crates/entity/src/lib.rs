@@ -1,5 +1,9 @@
 extern crate rocket;
 pub extern crate sea_orm;
 
+pub mod info_hash;
+pub mod metadata;
+pub mod release;
 pub mod stats;
+pub mod swarm_history;
 pub mod torrent;
@@ -0,0 +1,390 @@
+use regex::{Regex, RegexBuilder};
+use rocket::serde::{Deserialize, Serialize};
+use sea_orm::FromJsonQueryResult;
+use std::sync::OnceLock;
+
+use crate::torrent::{
+    CATEGORY_AUDIO, CATEGORY_BOOKS, CATEGORY_MOVIES, CATEGORY_OTHER, CATEGORY_PC, CATEGORY_TV,
+    CATEGORY_XXX,
+};
+
+// Fields parsed from a scene/p2p style release name such as
+// "Show.Name.S01E02.1080p.WEB-DL.DDP5.1.H.264-GROUP"
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+#[serde(crate = "rocket::serde")]
+pub struct Release {
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub season: Option<i32>,
+    pub episode: Option<i32>,
+    // air date of daily shows, as YYYY-MM-DD
+    pub air_date: Option<String>,
+    pub resolution: Option<String>,
+    pub video_codec: Option<String>,
+    pub source: Option<String>,
+    pub audio: Option<String>,
+    pub language: Option<String>,
+    pub group: Option<String>,
+}
+
+struct Patterns {
+    extension: Regex,
+    leading_group: Regex,
+    trailing_tags: Regex,
+    trailing_group: Regex,
+    season_episode: Regex,
+    cross_episode: Regex,
+    season: Regex,
+    episode: Regex,
+    anime_episode: Regex,
+    air_date: Regex,
+    year: Regex,
+    resolution: Regex,
+    video_codec: Regex,
+    source: Regex,
+    audio: Regex,
+    language: Regex,
+    books: Regex,
+    software: Regex,
+    xxx: Regex,
+}
+
+static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+
+// Wraps `pattern` so it only matches as a whole token between separators. The token itself
+// is capture group 1, the separators around it are consumed but not part of it.
+fn token(pattern: &str) -> Regex {
+    RegexBuilder::new(&format!(
+        r"(?:^|[\s._\-\[\](+])({})(?:$|[\s._\-\[\])+,])",
+        pattern
+    ))
+    .case_insensitive(true)
+    .build()
+    .unwrap()
+}
+
+fn patterns() -> &'static Patterns {
+    PATTERNS.get_or_init(|| Patterns {
+        extension: Regex::new(r"(?i)\.(mkv|mp4|avi|m4v|wmv|ts|iso|torrent)$").unwrap(),
+        leading_group: Regex::new(r"^\s*\[([^\]]+)\]\s*").unwrap(),
+        trailing_tags: Regex::new(r"(\s*\[[^\]]*\])+\s*$").unwrap(),
+        trailing_group: Regex::new(r"-([A-Za-z0-9]+)$").unwrap(),
+        season_episode: token(r"s(\d{1,2})[\s._-]?e(\d{1,3})(?:[\s._-]?e\d{1,3})*"),
+        cross_episode: token(r"(\d{1,2})x(\d{2,3})"),
+        season: token(r"s(\d{1,2})|season[\s._-]?(\d{1,2})"),
+        episode: token(r"(?:e|ep|episode)[\s._-]?(\d{1,3})"),
+        anime_episode: Regex::new(r"\s-\s(\d{2,4})(?:v\d)?(?:\s|$)").unwrap(),
+        air_date: token(r"((?:19|20)\d{2})[\s._-](0[1-9]|1[0-2])[\s._-](0[1-9]|[12]\d|3[01])"),
+        year: token(r"(?:19|20)\d{2}"),
+        resolution: token(r"2160p|1080p|1080i|720p|576p|480p|4k|uhd"),
+        video_codec: token(r"[xh][\s.]?26[45]|hevc|avc|xvid|divx|av1|vp9|mpeg-?2"),
+        source: token(
+            r"web[\s.-]?dl|web[\s.-]?rip|web|blu[\s.-]?ray|bdrip|brrip|bdremux|remux|hdtv|pdtv|dvdrip|dvd[\s.-]?r|dvd|hdrip|hdcam|cam|hdts|telesync|ts|screener|scr",
+        ),
+        audio: token(
+            r"dts[\s.-]?hd(?:[\s.-]?ma)?|dts[\s.-]?x|dts|truehd|atmos|ddp?[\s.]?[257][\s.]?[01]|dd\+|e-?ac-?3|ac-?3|aac(?:[\s.]?[257][\s.]?[01])?|flac|mp3|opus|lpcm",
+        ),
+        language: token(
+            r"multi|dual[\s.-]?audio|english|french|truefrench|vostfr|german|italian|spanish|castellano|latino|russian|japanese|korean|chinese|hindi|portuguese|dutch|polish|swedish|turkish",
+        ),
+        books: token(r"pdf|epub|mobi|azw3|cbr|cbz|ebook"),
+        software: Regex::new(r"(?:^|[\s._\-\[\](])(PC|MAC|macOS|Windows|x64|x86)(?:$|[\s._\-\[\])])")
+            .unwrap(),
+        xxx: Regex::new(r"(?:^|[\s._\-\[\](])(XXX)(?:$|[\s._\-\[\])])").unwrap(),
+    })
+}
+
+// Every match of `re` in `text`, as (start, end, captures) of the token. Matching restarts
+// right after each token, so separators shared by adjacent tokens are not lost.
+fn find_all<'t>(re: &Regex, text: &'t str) -> Vec<(usize, usize, regex::Captures<'t>)> {
+    let mut found = vec![];
+    let mut pos = 0;
+
+    while pos <= text.len() {
+        let Some(captures) = re.captures_at(text, pos) else {
+            break;
+        };
+
+        let token = captures.get(1).unwrap();
+        let next = token.end().max(pos + 1);
+
+        found.push((token.start(), token.end(), captures));
+
+        pos = next;
+
+        while pos < text.len() && !text.is_char_boundary(pos) {
+            pos += 1;
+        }
+    }
+
+    found
+}
+
+fn first_number(captures: &regex::Captures, from: usize) -> Option<i32> {
+    (from..captures.len())
+        .filter_map(|i| captures.get(i))
+        .find_map(|m| m.as_str().parse::<i32>().ok())
+}
+
+fn normalise_resolution(resolution: &str) -> String {
+    match resolution.to_lowercase().as_str() {
+        "4k" | "uhd" => "2160p".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn normalise_video_codec(codec: &str) -> String {
+    let compact = codec.to_lowercase().replace([' ', '.', '-'], "");
+
+    match compact.as_str() {
+        "x264" => "x264",
+        "x265" => "x265",
+        "h264" | "avc" => "H.264",
+        "h265" | "hevc" => "H.265",
+        "xvid" => "XviD",
+        "divx" => "DivX",
+        "av1" => "AV1",
+        "vp9" => "VP9",
+        _ => "MPEG-2",
+    }
+    .to_string()
+}
+
+fn normalise_source(source: &str) -> String {
+    let compact = source.to_lowercase().replace([' ', '.', '-'], "");
+
+    match compact.as_str() {
+        "webdl" => "WEB-DL",
+        "webrip" => "WEBRip",
+        "web" => "WEB",
+        "bluray" => "BluRay",
+        "bdrip" => "BDRip",
+        "brrip" => "BRRip",
+        "bdremux" | "remux" => "Remux",
+        "hdtv" => "HDTV",
+        "pdtv" => "PDTV",
+        "dvdrip" => "DVDRip",
+        "dvdr" | "dvd" => "DVD",
+        "hdrip" => "HDRip",
+        "hdcam" | "cam" => "CAM",
+        "hdts" | "telesync" | "ts" => "Telesync",
+        _ => "Screener",
+    }
+    .to_string()
+}
+
+fn normalise_audio(audio: &str) -> String {
+    let lower = audio.to_lowercase();
+    let compact = lower.replace([' ', '.', '-'], "");
+
+    if compact.starts_with("dtshd") {
+        return "DTS-HD".to_string();
+    }
+
+    if compact.starts_with("ddp") || compact == "dd+" || compact == "eac3" {
+        return "EAC3".to_string();
+    }
+
+    if compact.starts_with("dd") || compact == "ac3" {
+        return "AC3".to_string();
+    }
+
+    if compact.starts_with("aac") {
+        return "AAC".to_string();
+    }
+
+    match compact.as_str() {
+        "dtsx" => "DTS:X",
+        "dts" => "DTS",
+        "truehd" => "TrueHD",
+        "atmos" => "Atmos",
+        "flac" => "FLAC",
+        "mp3" => "MP3",
+        "opus" => "Opus",
+        _ => "LPCM",
+    }
+    .to_string()
+}
+
+fn normalise_language(language: &str) -> String {
+    let compact = language.to_lowercase().replace([' ', '.', '-'], "");
+
+    match compact.as_str() {
+        "multi" => "Multi".to_string(),
+        "dualaudio" => "Dual Audio".to_string(),
+        "truefrench" | "vostfr" => "French".to_string(),
+        "castellano" => "Spanish".to_string(),
+        "latino" => "Latin Spanish".to_string(),
+        other => {
+            let mut chars = other.chars();
+
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+    }
+}
+
+// Words that follow a dash in release names without being the release group
+static NOT_GROUPS: &[&str] = &[
+    "dl", "rip", "ray", "hd", "ma", "x", "audio", "sub", "subs", "amd64", "arm64", "i386", "x64",
+    "x86",
+];
+
+impl Release {
+    pub fn parse(name: &str) -> Self {
+        let patterns = patterns();
+        let mut release = Release::default();
+
+        let mut text = patterns.extension.replace(name.trim(), "").to_string();
+
+        if let Some(captures) = patterns.leading_group.captures(&text) {
+            release.group = Some(captures[1].trim().to_string());
+            text = text[captures.get(0).unwrap().end()..].to_string();
+        }
+
+        // trailing "[1080p][FLAC]" style tags hold details but never the title or group
+        let tags = patterns
+            .trailing_tags
+            .find(&text)
+            .map(|tags| tags.as_str().to_string())
+            .unwrap_or_default();
+
+        text = text[..text.len() - tags.len()].trim().to_string();
+
+        if release.group.is_none() {
+            if let Some(captures) = patterns.trailing_group.captures(&text) {
+                let group = captures[1].to_string();
+
+                if !NOT_GROUPS.contains(&group.to_lowercase().as_str()) {
+                    text = text[..captures.get(0).unwrap().start()].to_string();
+                    release.group = Some(group);
+                }
+            }
+        }
+
+        // where the title ends: the earliest season, episode or year marker
+        let mut title_end: Option<usize> = None;
+        let mut mark = |start: usize| {
+            title_end = Some(title_end.map_or(start, |end: usize| end.min(start)));
+        };
+
+        if let Some((start, _, captures)) = find_all(&patterns.season_episode, &text)
+            .into_iter()
+            .next()
+            .or_else(|| find_all(&patterns.cross_episode, &text).into_iter().next())
+        {
+            release.season = first_number(&captures, 2);
+            release.episode = captures.get(3).and_then(|m| m.as_str().parse().ok());
+            mark(start);
+        } else {
+            if let Some((start, _, captures)) = find_all(&patterns.season, &text).into_iter().next()
+            {
+                release.season = first_number(&captures, 2);
+                mark(start);
+            }
+
+            if let Some((start, _, captures)) =
+                find_all(&patterns.episode, &text).into_iter().next()
+            {
+                release.episode = first_number(&captures, 2);
+                mark(start);
+            } else if let Some(captures) = patterns.anime_episode.captures(&text) {
+                release.episode = captures[1].parse().ok();
+                mark(captures.get(0).unwrap().start());
+            }
+        }
+
+        if let Some((start, _, captures)) = find_all(&patterns.air_date, &text).into_iter().next() {
+            release.air_date = Some(format!(
+                "{}-{}-{}",
+                &captures[2], &captures[3], &captures[4]
+            ));
+            mark(start);
+        }
+
+        // a year at the very start is part of the title ("2012", "1917"), otherwise the
+        // last one wins so titles containing a year still parse
+        if let Some((start, _, captures)) = find_all(&patterns.year, &text)
+            .into_iter()
+            .filter(|(start, _, _)| *start > 0)
+            .last()
+        {
+            release.year = captures[1].parse().ok();
+            mark(start);
+        }
+
+        // technical details are only looked for after the title when we know where it ends,
+        // so titles like "Charlotte's Web" do not get a source
+        let details_from = title_end.unwrap_or(0);
+        let details = format!("{} {}", &text[details_from..], tags);
+        let details = details.as_str();
+        let mut details_start = text.len() - details_from;
+
+        let mut first = |re: &Regex, normalise: fn(&str) -> String| -> Option<String> {
+            let (start, end, _) = find_all(re, details).into_iter().next()?;
+
+            details_start = details_start.min(start);
+
+            Some(normalise(&details[start..end]))
+        };
+
+        release.resolution = first(&patterns.resolution, normalise_resolution);
+        release.video_codec = first(&patterns.video_codec, normalise_video_codec);
+        release.source = first(&patterns.source, normalise_source);
+        release.audio = first(&patterns.audio, normalise_audio);
+        release.language = first(&patterns.language, normalise_language);
+
+        let title_end = title_end.unwrap_or(details_from + details_start);
+
+        let title = text[..title_end]
+            .replace(['.', '_'], " ")
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        let title = title.trim_matches(|c: char| c == '-' || c == '(' || c == '[' || c == ' ');
+
+        if !title.is_empty() {
+            release.title = Some(title.to_string());
+        }
+
+        release
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.resolution.is_some() || self.video_codec.is_some() || self.source.is_some()
+    }
+
+    // Newznab category for the release, `name` is the full name it was parsed from
+    pub fn category(&self, name: &str) -> i32 {
+        let patterns = patterns();
+
+        if patterns.xxx.is_match(name) {
+            return CATEGORY_XXX;
+        }
+
+        if self.season.is_some() || self.episode.is_some() || self.air_date.is_some() {
+            return CATEGORY_TV;
+        }
+
+        if self.is_video() {
+            return CATEGORY_MOVIES;
+        }
+
+        if matches!(self.audio.as_deref(), Some("FLAC" | "MP3" | "Opus")) {
+            return CATEGORY_AUDIO;
+        }
+
+        if patterns.books.is_match(name) {
+            return CATEGORY_BOOKS;
+        }
+
+        if patterns.software.is_match(name) {
+            return CATEGORY_PC;
+        }
+
+        CATEGORY_OTHER
+    }
+}
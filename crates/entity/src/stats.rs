@@ -10,6 +10,7 @@ pub enum StatType {
     ScrapedTorrents,
     #[sea_orm(string_value = "queue_torrent_info")]
     QueueInfo,
+    // not maintained as a counter, the tracker queue depends on how old scrapes are
     #[sea_orm(string_value = "queue_torrent_trackers")]
     QueueTrackers,
     #[sea_orm(string_value = "stale_torrents")]
@@ -0,0 +1,21 @@
+use rocket::serde::{Deserialize, Serialize};
+use sea_orm::entity::prelude::*;
+
+// One sample per tracker scrape, older samples are averaged down to one per day
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+#[sea_orm(table_name = "torrent_swarm_history")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[serde(skip)]
+    pub torrent_id: i32,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub recorded_at: DateTime,
+    pub seeders: i32,
+    pub leechers: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,8 @@
 use rocket::serde::{Deserialize, Serialize};
 use sea_orm::{entity::prelude::*, FromJsonQueryResult};
 
+use crate::release::Release;
+
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 #[sea_orm(table_name = "torrents")]
@@ -9,18 +11,30 @@ pub struct Model {
     pub id: i32,
     pub name: Option<String>,
     pub info_hash: String,
-    pub size: i32,
-    pub files: Vec<String>,
+    // in bytes
+    pub size: i64,
+    pub files: Files,
     pub added_at: DateTime,
     pub seeders: i32,
     pub leechers: i32,
     pub trackers: Trackers,
     #[sea_orm(indexed)]
+    pub category: i32,
+    pub release: Option<Release>,
+    #[sea_orm(indexed)]
     pub last_scrape: Option<DateTime>,
     #[sea_orm(indexed)]
     pub last_tracker_scrape: Option<DateTime>,
     #[sea_orm(indexed)]
     pub last_stale: Option<DateTime>,
+    // set while a worker has claimed the torrent from the info or tracker queue
+    #[serde(skip)]
+    #[sea_orm(indexed)]
+    pub leased_until: Option<DateTime>,
+    // failed metadata fetches, the torrent stays out of the info queue until next_attempt_at
+    pub metadata_attempts: i32,
+    pub next_attempt_at: Option<DateTime>,
+    pub metadata_error: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -39,35 +53,51 @@ pub struct Tracker {
     pub last_scrape: DateTime,
 }
 
-impl Model {
-    pub fn get_category(&self) -> (&str, &str) {
-        let name = self.name.as_ref().unwrap();
-        let tvshow_re = regex::Regex::new(r"(.+?)(S(\d{2})|E(\d{2})|Season|Episode)(.*)").unwrap();
-
-        if tvshow_re.is_match(name) {
-            return ("TV", "5000");
-        }
-
-        if name.contains("1080p") || name.contains("720p") {
-            return ("Movies", "2000");
-        }
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct Files(pub Vec<File>);
 
-        if name.contains("MP3") || name.contains("FLAC") {
-            return ("Audio", "3000");
-        }
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct File {
+    pub path: String,
+    // in bytes
+    pub length: i64,
+    // lowercase, without the dot
+    pub extension: Option<String>,
+}
 
-        if name.contains("PDF") || name.contains("EPUB") {
-            return ("Books", "7000");
-        }
+impl File {
+    pub fn new(path: String, length: i64) -> Self {
+        let extension = path
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+            .filter(|extension| {
+                !extension.is_empty()
+                    && extension.len() <= 8
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+            .map(|extension| extension.to_lowercase());
 
-        if name.contains("PC") || name.contains("MAC") {
-            return ("PC", "4000");
+        Self {
+            path,
+            length,
+            extension,
         }
+    }
+}
 
-        if name.contains("XXX") {
-            return ("XXX", "6000");
-        }
+// Newznab category ids
+pub const CATEGORY_MOVIES: i32 = 2000;
+pub const CATEGORY_AUDIO: i32 = 3000;
+pub const CATEGORY_PC: i32 = 4000;
+pub const CATEGORY_TV: i32 = 5000;
+pub const CATEGORY_XXX: i32 = 6000;
+pub const CATEGORY_BOOKS: i32 = 7000;
+pub const CATEGORY_OTHER: i32 = 8000;
 
-        ("Other", "8000")
+impl Model {
+    pub fn category_for_name(name: &str) -> i32 {
+        Release::parse(name).category(name)
     }
 }
@@ -0,0 +1,164 @@
+use oxidized_entity::info_hash::InfoHash;
+
+const V1: &str = "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A";
+const V1_BASE32: &str = "YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK";
+const V2: &str = "181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA415523";
+
+struct Case {
+    input: &'static str,
+    // uppercase hex the input parses to
+    expected: &'static str,
+    v2: bool,
+}
+
+const VALID: &[Case] = &[
+    // v1 hex
+    Case {
+        input: "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "c12fe1c06bba254a9dc9f519b335aa7c1367a88a",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "  c12fe1c06bba254a9dc9f519b335aa7c1367a88a\n",
+        expected: V1,
+        v2: false,
+    },
+    // v1 base32
+    Case {
+        input: "YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "yex6dqdlxisuvhoj6um3gnnkpqjwpkek",
+        expected: V1,
+        v2: false,
+    },
+    // v2 hex and multihash
+    Case {
+        input: "181226b2d004b8f1ba9db868228a9768587c9f6d8ce606e85b015e9dca415523",
+        expected: V2,
+        v2: true,
+    },
+    Case {
+        input: "1220181226b2d004b8f1ba9db868228a9768587c9f6d8ce606e85b015e9dca415523",
+        expected: V2,
+        v2: true,
+    },
+    // magnets
+    Case {
+        input: "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a&dn=Name",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "MAGNET:?dn=Name&xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "magnet:?xt=urn%3Abtih%3Ac12fe1c06bba254a9dc9f519b335aa7c1367a88a",
+        expected: V1,
+        v2: false,
+    },
+    Case {
+        input: "magnet:?xt=urn:btmh:1220181226b2d004b8f1ba9db868228a9768587c9f6d8ce606e85b015e9dca415523",
+        expected: V2,
+        v2: true,
+    },
+    // hybrid torrents are looked up by their v1 hash
+    Case {
+        input: "magnet:?xt.1=urn:btmh:1220181226b2d004b8f1ba9db868228a9768587c9f6d8ce606e85b015e9dca415523&xt.2=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a",
+        expected: V1,
+        v2: false,
+    },
+];
+
+const INVALID: &[&str] = &[
+    "",
+    "   ",
+    // one character short and long
+    "C12FE1C06BBA254A9DC9F519B335AA7C1367A88",
+    "C12FE1C06BBA254A9DC9F519B335AA7C1367A88AA",
+    // not hex
+    "G12FE1C06BBA254A9DC9F519B335AA7C1367A88A",
+    // 0, 1, 8 and 9 are not base32
+    "YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKE1",
+    // v2 one character short
+    "181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA41552",
+    // a multihash other than sha2-256
+    "1320181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA415523",
+    "magnet:?dn=Name",
+    "magnet:?xt=urn:btih:C12FE1C06BBA254A9DC9F519B335AA7C1367A88",
+    "magnet:?xt=urn:btmh:181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA415523",
+    "magnet:?xt=urn:sha1:C12FE1C06BBA254A9DC9F519B335AA7C1367A88A",
+    "héllo wörld ünicode input that is forty",
+];
+
+#[test]
+fn parses_valid_info_hashes() {
+    for case in VALID {
+        let info_hash =
+            InfoHash::parse(case.input).unwrap_or_else(|e| panic!("{}: {}", case.input, e));
+
+        assert_eq!(info_hash.to_string(), case.expected, "{}", case.input);
+        assert_eq!(
+            matches!(info_hash, InfoHash::V2(_)),
+            case.v2,
+            "{}",
+            case.input
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_info_hashes() {
+    for input in INVALID {
+        assert!(InfoHash::parse(input).is_err(), "{:?} was accepted", input);
+    }
+}
+
+#[test]
+fn round_trips_through_display() {
+    for case in VALID {
+        let info_hash = InfoHash::parse(case.input).unwrap();
+
+        assert_eq!(InfoHash::parse(&info_hash.to_string()), Ok(info_hash));
+        assert_eq!(info_hash.to_string().parse::<InfoHash>(), Ok(info_hash));
+    }
+}
+
+#[test]
+fn base32_and_hex_agree() {
+    assert_eq!(InfoHash::parse(V1_BASE32), InfoHash::parse(V1));
+}
+
+#[test]
+fn truncates_v2_hashes() {
+    let v1 = InfoHash::parse(V1).unwrap();
+    let v2 = InfoHash::parse(V2).unwrap();
+
+    assert_eq!(InfoHash::V1(v1.truncated()), v1);
+    assert_eq!(InfoHash::V1(v2.truncated()).to_string(), &V2[..40]);
+}
+
+#[test]
+fn builds_magnet_topics() {
+    let v1 = InfoHash::parse(V1).unwrap();
+    let v2 = InfoHash::parse(V2).unwrap();
+
+    assert_eq!(v1.magnet_topic(), format!("urn:btih:{}", V1));
+    assert_eq!(v2.magnet_topic(), format!("urn:btmh:1220{}", V2));
+
+    // the topics parse back to the same hash
+    for info_hash in [v1, v2] {
+        let magnet = format!("magnet:?xt={}", info_hash.magnet_topic());
+
+        assert_eq!(InfoHash::parse(&magnet), Ok(info_hash));
+    }
+}
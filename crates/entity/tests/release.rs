@@ -0,0 +1,708 @@
+use oxidized_entity::{
+    release::Release,
+    torrent::{
+        CATEGORY_AUDIO, CATEGORY_BOOKS, CATEGORY_MOVIES, CATEGORY_OTHER, CATEGORY_PC, CATEGORY_TV,
+        CATEGORY_XXX,
+    },
+};
+
+struct Case {
+    name: &'static str,
+    title: Option<&'static str>,
+    year: Option<i32>,
+    season: Option<i32>,
+    episode: Option<i32>,
+    air_date: Option<&'static str>,
+    resolution: Option<&'static str>,
+    video_codec: Option<&'static str>,
+    source: Option<&'static str>,
+    audio: Option<&'static str>,
+    language: Option<&'static str>,
+    group: Option<&'static str>,
+    category: i32,
+}
+
+const EMPTY: Case = Case {
+    name: "",
+    title: None,
+    year: None,
+    season: None,
+    episode: None,
+    air_date: None,
+    resolution: None,
+    video_codec: None,
+    source: None,
+    audio: None,
+    language: None,
+    group: None,
+    category: CATEGORY_OTHER,
+};
+
+const CASES: &[Case] = &[
+    // scene tv
+    Case {
+        name: "Show.Name.S01E02.1080p.WEB-DL.DDP5.1.H.264-GROUP",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(2),
+        resolution: Some("1080p"),
+        video_codec: Some("H.264"),
+        source: Some("WEB-DL"),
+        audio: Some("EAC3"),
+        group: Some("GROUP"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "The.Last.of.Us.S01E09.720p.HDTV.x264-SYNCOPY",
+        title: Some("The Last of Us"),
+        season: Some(1),
+        episode: Some(9),
+        resolution: Some("720p"),
+        video_codec: Some("x264"),
+        source: Some("HDTV"),
+        group: Some("SYNCOPY"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Severance.S02E10.2160p.ATVP.WEB-DL.DDP5.1.Atmos.DV.HDR.H.265-FLUX.mkv",
+        title: Some("Severance"),
+        season: Some(2),
+        episode: Some(10),
+        resolution: Some("2160p"),
+        video_codec: Some("H.265"),
+        source: Some("WEB-DL"),
+        audio: Some("EAC3"),
+        group: Some("FLUX"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Doctor.Who.2005.S13E01.1080p.BluRay.x264-SHORTBREHD",
+        title: Some("Doctor Who"),
+        year: Some(2005),
+        season: Some(13),
+        episode: Some(1),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("BluRay"),
+        group: Some("SHORTBREHD"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "show_name_s03e04_480p_hdtv_xvid",
+        title: Some("show name"),
+        season: Some(3),
+        episode: Some(4),
+        resolution: Some("480p"),
+        video_codec: Some("XviD"),
+        source: Some("HDTV"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show Name S01 E05 720p WEBRip AAC",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(5),
+        resolution: Some("720p"),
+        source: Some("WEBRip"),
+        audio: Some("AAC"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.S01E01E02.1080p.WEB.h264-GRP",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(1),
+        resolution: Some("1080p"),
+        video_codec: Some("H.264"),
+        source: Some("WEB"),
+        group: Some("GRP"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.1x05.HDTV.XviD-LOL",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(5),
+        video_codec: Some("XviD"),
+        source: Some("HDTV"),
+        group: Some("LOL"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.10x120.PDTV.x264",
+        title: Some("Show Name"),
+        season: Some(10),
+        episode: Some(120),
+        video_codec: Some("x264"),
+        source: Some("PDTV"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.S02.1080p.BluRay.x265-RARBG",
+        title: Some("Show Name"),
+        season: Some(2),
+        resolution: Some("1080p"),
+        video_codec: Some("x265"),
+        source: Some("BluRay"),
+        group: Some("RARBG"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show Name Season 3 Complete 720p",
+        title: Some("Show Name"),
+        season: Some(3),
+        resolution: Some("720p"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.Season.1.MULTi.1080p.WEB.x264",
+        title: Some("Show Name"),
+        season: Some(1),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("WEB"),
+        language: Some("Multi"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show Name Episode 7 HDTV",
+        title: Some("Show Name"),
+        episode: Some(7),
+        source: Some("HDTV"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.E12.720p.HDTV",
+        title: Some("Show Name"),
+        episode: Some(12),
+        resolution: Some("720p"),
+        source: Some("HDTV"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "The.Daily.Show.2024.05.14.720p.WEB.h264-EDITH",
+        title: Some("The Daily Show"),
+        year: Some(2024),
+        air_date: Some("2024-05-14"),
+        resolution: Some("720p"),
+        video_codec: Some("H.264"),
+        source: Some("WEB"),
+        group: Some("EDITH"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.S05E03.German.DL.1080p.WEB.x264-WAYNE",
+        title: Some("Show Name"),
+        season: Some(5),
+        episode: Some(3),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("WEB"),
+        language: Some("German"),
+        group: Some("WAYNE"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.S01E01.FRENCH.720p.HDTV.x264-GRP",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(1),
+        resolution: Some("720p"),
+        video_codec: Some("x264"),
+        source: Some("HDTV"),
+        language: Some("French"),
+        group: Some("GRP"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "Show.Name.S01E01.VOSTFR.1080p.WEB",
+        title: Some("Show Name"),
+        season: Some(1),
+        episode: Some(1),
+        resolution: Some("1080p"),
+        source: Some("WEB"),
+        language: Some("French"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    // anime
+    Case {
+        name: "[SubsPlease] Frieren - 05 (1080p) [ABCD1234].mkv",
+        title: Some("Frieren"),
+        episode: Some(5),
+        resolution: Some("1080p"),
+        group: Some("SubsPlease"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "[Erai-raws] Show Name - 112 [720p][Multiple Subtitle].mkv",
+        title: Some("Show Name"),
+        episode: Some(112),
+        resolution: Some("720p"),
+        group: Some("Erai-raws"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "[Judas] Show Name - S02E03 (1080p HEVC x265 10-bit)",
+        title: Some("Show Name"),
+        season: Some(2),
+        episode: Some(3),
+        resolution: Some("1080p"),
+        video_codec: Some("H.265"),
+        group: Some("Judas"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "[Group] Show Name - 01v2 [1080p]",
+        title: Some("Show Name"),
+        episode: Some(1),
+        resolution: Some("1080p"),
+        group: Some("Group"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    Case {
+        name: "[Anime Time] Show Name (Season 1) [Dual Audio][1080p][HEVC 10bit]",
+        title: Some("Show Name"),
+        season: Some(1),
+        resolution: Some("1080p"),
+        video_codec: Some("H.265"),
+        language: Some("Dual Audio"),
+        group: Some("Anime Time"),
+        category: CATEGORY_TV,
+        ..EMPTY
+    },
+    // movies
+    Case {
+        name: "The.Matrix.1999.1080p.BluRay.x264.DTS-WiKi",
+        title: Some("The Matrix"),
+        year: Some(1999),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("BluRay"),
+        audio: Some("DTS"),
+        group: Some("WiKi"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Dune.Part.Two.2024.2160p.UHD.BluRay.REMUX.HDR.HEVC.TrueHD.7.1.Atmos-FGT",
+        title: Some("Dune Part Two"),
+        year: Some(2024),
+        resolution: Some("2160p"),
+        video_codec: Some("H.265"),
+        source: Some("BluRay"),
+        audio: Some("TrueHD"),
+        group: Some("FGT"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Blade Runner 2049 (2017) 1080p BluRay x265 10bit DDP 5.1",
+        title: Some("Blade Runner 2049"),
+        year: Some(2017),
+        resolution: Some("1080p"),
+        video_codec: Some("x265"),
+        source: Some("BluRay"),
+        audio: Some("EAC3"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "2001.A.Space.Odyssey.1968.720p.BRRip.x264.AAC-ETRG",
+        title: Some("2001 A Space Odyssey"),
+        year: Some(1968),
+        resolution: Some("720p"),
+        video_codec: Some("x264"),
+        source: Some("BRRip"),
+        audio: Some("AAC"),
+        group: Some("ETRG"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "1917.2019.1080p.WEBRip.x264.AAC5.1-YTS",
+        title: Some("1917"),
+        year: Some(2019),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("WEBRip"),
+        audio: Some("AAC"),
+        group: Some("YTS"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Charlotte's Web 2006 DVDRip XviD",
+        title: Some("Charlotte's Web"),
+        year: Some(2006),
+        video_codec: Some("XviD"),
+        source: Some("DVDRip"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Oppenheimer.2023.IMAX.2160p.WEB-DL.DTS-HD.MA.5.1.x265-GROUP",
+        title: Some("Oppenheimer"),
+        year: Some(2023),
+        resolution: Some("2160p"),
+        video_codec: Some("x265"),
+        source: Some("WEB-DL"),
+        audio: Some("DTS-HD"),
+        group: Some("GROUP"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2022.HDCAM.x264-NOGRP",
+        title: Some("Movie Name"),
+        year: Some(2022),
+        video_codec: Some("x264"),
+        source: Some("CAM"),
+        group: Some("NOGRP"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2021.TELESYNC.XviD",
+        title: Some("Movie Name"),
+        year: Some(2021),
+        video_codec: Some("XviD"),
+        source: Some("Telesync"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie Name (2015) [1080p] [YTS.MX]",
+        title: Some("Movie Name"),
+        year: Some(2015),
+        resolution: Some("1080p"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2019.MULTi.1080p.BluRay.x264-LOST",
+        title: Some("Movie Name"),
+        year: Some(2019),
+        resolution: Some("1080p"),
+        video_codec: Some("x264"),
+        source: Some("BluRay"),
+        language: Some("Multi"),
+        group: Some("LOST"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2018.TRUEFRENCH.DVDRip.XviD-GRP",
+        title: Some("Movie Name"),
+        year: Some(2018),
+        video_codec: Some("XviD"),
+        source: Some("DVDRip"),
+        language: Some("French"),
+        group: Some("GRP"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie Name 2010 Castellano 720p",
+        title: Some("Movie Name"),
+        year: Some(2010),
+        resolution: Some("720p"),
+        language: Some("Spanish"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2020.Dual.Audio.Hindi.720p.WEB-DL",
+        title: Some("Movie Name"),
+        year: Some(2020),
+        resolution: Some("720p"),
+        source: Some("WEB-DL"),
+        language: Some("Dual Audio"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2017.4K.HDR.AV1.Opus-GRP",
+        title: Some("Movie Name"),
+        year: Some(2017),
+        resolution: Some("2160p"),
+        video_codec: Some("AV1"),
+        audio: Some("Opus"),
+        group: Some("GRP"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2009.DVD9.MPEG-2.AC3",
+        title: Some("Movie Name"),
+        year: Some(2009),
+        video_codec: Some("MPEG-2"),
+        audio: Some("AC3"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2016.BDRemux.1080p.AVC.DTS-X",
+        title: Some("Movie Name"),
+        year: Some(2016),
+        resolution: Some("1080p"),
+        video_codec: Some("H.264"),
+        source: Some("Remux"),
+        audio: Some("DTS:X"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2014.576p.DVDR.DivX.LPCM",
+        title: Some("Movie Name"),
+        year: Some(2014),
+        resolution: Some("576p"),
+        video_codec: Some("DivX"),
+        source: Some("DVD"),
+        audio: Some("LPCM"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie Name 1080p",
+        title: Some("Movie Name"),
+        resolution: Some("1080p"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    Case {
+        name: "Movie.Name.2011.SCREENER.x264",
+        title: Some("Movie Name"),
+        year: Some(2011),
+        video_codec: Some("x264"),
+        source: Some("Screener"),
+        category: CATEGORY_MOVIES,
+        ..EMPTY
+    },
+    // audio
+    Case {
+        name: "Artist - Album (2020) [FLAC]",
+        title: Some("Artist - Album"),
+        year: Some(2020),
+        audio: Some("FLAC"),
+        category: CATEGORY_AUDIO,
+        ..EMPTY
+    },
+    Case {
+        name: "Artist - Album 2019 FLAC 24bit",
+        title: Some("Artist - Album"),
+        year: Some(2019),
+        audio: Some("FLAC"),
+        category: CATEGORY_AUDIO,
+        ..EMPTY
+    },
+    Case {
+        name: "Artist.-.Album.2015.MP3.320kbps",
+        title: Some("Artist - Album"),
+        year: Some(2015),
+        audio: Some("MP3"),
+        category: CATEGORY_AUDIO,
+        ..EMPTY
+    },
+    Case {
+        name: "Various Artists - Greatest Hits MP3",
+        title: Some("Various Artists - Greatest Hits"),
+        audio: Some("MP3"),
+        category: CATEGORY_AUDIO,
+        ..EMPTY
+    },
+    Case {
+        name: "Podcast Episode Collection opus",
+        title: Some("Podcast Episode Collection"),
+        audio: Some("Opus"),
+        category: CATEGORY_AUDIO,
+        ..EMPTY
+    },
+    // books
+    Case {
+        name: "Author Name - Book Title (2018) EPUB",
+        title: Some("Author Name - Book Title"),
+        year: Some(2018),
+        category: CATEGORY_BOOKS,
+        ..EMPTY
+    },
+    Case {
+        name: "Programming Rust 2nd Edition.pdf",
+        title: Some("Programming Rust 2nd Edition pdf"),
+        category: CATEGORY_BOOKS,
+        ..EMPTY
+    },
+    Case {
+        name: "Comic Name 001 (2021) (digital).cbz",
+        title: Some("Comic Name 001"),
+        year: Some(2021),
+        category: CATEGORY_BOOKS,
+        ..EMPTY
+    },
+    Case {
+        name: "Some eBook Collection",
+        title: Some("Some eBook Collection"),
+        category: CATEGORY_BOOKS,
+        ..EMPTY
+    },
+    // software
+    Case {
+        name: "Adobe Photoshop 2024 v25.0 x64",
+        title: Some("Adobe Photoshop"),
+        year: Some(2024),
+        category: CATEGORY_PC,
+        ..EMPTY
+    },
+    Case {
+        name: "Game.Name-CODEX PC",
+        title: Some("Game Name-CODEX PC"),
+        category: CATEGORY_PC,
+        ..EMPTY
+    },
+    Case {
+        name: "Final Cut Pro 10.6 macOS",
+        title: Some("Final Cut Pro 10 6 macOS"),
+        category: CATEGORY_PC,
+        ..EMPTY
+    },
+    Case {
+        name: "Microsoft Office 2021 Windows",
+        title: Some("Microsoft Office"),
+        year: Some(2021),
+        category: CATEGORY_PC,
+        ..EMPTY
+    },
+    // names the old substring rules got wrong
+    Case {
+        name: "PCB Design Tutorial",
+        title: Some("PCB Design Tutorial"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    Case {
+        name: "MACHINE LEARNING NOTES",
+        title: Some("MACHINE LEARNING NOTES"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    Case {
+        name: "Essays on Society",
+        title: Some("Essays on Society"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    Case {
+        name: "Season's Greetings Cards",
+        title: Some("Season's Greetings Cards"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    // adult
+    Case {
+        name: "Studio.24.05.14.Some.Name.XXX.1080p.MP4-WRB",
+        title: Some("Studio 24 05 14 Some Name XXX"),
+        resolution: Some("1080p"),
+        group: Some("WRB"),
+        category: CATEGORY_XXX,
+        ..EMPTY
+    },
+    Case {
+        name: "Some.Show.S01E01.XXX.720p.WEB",
+        title: Some("Some Show"),
+        season: Some(1),
+        episode: Some(1),
+        resolution: Some("720p"),
+        source: Some("WEB"),
+        category: CATEGORY_XXX,
+        ..EMPTY
+    },
+    // other
+    Case {
+        name: "random_files",
+        title: Some("random files"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    Case {
+        name: "ubuntu-24.04-desktop-amd64.iso",
+        title: Some("ubuntu-24 04-desktop-amd64"),
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+    Case {
+        name: "",
+        category: CATEGORY_OTHER,
+        ..EMPTY
+    },
+];
+
+#[test]
+fn parses_release_names() {
+    let mut failures = vec![];
+
+    for case in CASES {
+        let release = Release::parse(case.name);
+
+        let expected = Release {
+            title: case.title.map(String::from),
+            year: case.year,
+            season: case.season,
+            episode: case.episode,
+            air_date: case.air_date.map(String::from),
+            resolution: case.resolution.map(String::from),
+            video_codec: case.video_codec.map(String::from),
+            source: case.source.map(String::from),
+            audio: case.audio.map(String::from),
+            language: case.language.map(String::from),
+            group: case.group.map(String::from),
+        };
+
+        if release != expected {
+            failures.push(format!(
+                "{:?}\n  expected {:?}\n  got      {:?}",
+                case.name, expected, release
+            ));
+        }
+
+        let category = release.category(case.name);
+
+        if category != case.category {
+            failures.push(format!(
+                "{:?}\n  expected category {}\n  got      {}",
+                case.name, case.category, category
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn parsed_release_roundtrips_through_json() {
+    let release = Release::parse("Show.Name.S01E02.1080p.WEB-DL.DDP5.1.H.264-GROUP");
+
+    let json = serde_json::to_string(&release).unwrap();
+
+    assert_eq!(serde_json::from_str::<Release>(&json).unwrap(), release);
+}
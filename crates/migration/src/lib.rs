@@ -1,6 +1,16 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220120_000001_create_torrents_table;
+mod m20261018_000001_add_torrents_search_vector;
+mod m20261018_000002_add_torrents_category;
+mod m20261018_000003_add_torrents_release;
+mod m20261018_000004_convert_torrents_files_to_jsonb;
+mod m20261018_000005_store_torrents_size_in_bytes;
+mod m20261018_000006_create_torrent_metadata_table;
+mod m20261018_000007_seed_stats_counters;
+mod m20261018_000008_create_torrent_swarm_history_table;
+mod m20261018_000009_add_torrents_leased_until;
+mod m20261018_000010_add_torrents_metadata_attempts;
 
 pub struct Migrator;
 
@@ -20,6 +30,29 @@ pub enum Torrents {
     LastTrackerScrape,
     LastStale,
     Trackers,
+    SearchVector,
+    Category,
+    Release,
+    LeasedUntil,
+    MetadataAttempts,
+    NextAttemptAt,
+    MetadataError,
+}
+
+#[derive(DeriveIden)]
+pub enum TorrentMetadata {
+    Table,
+    TorrentId,
+    Info,
+}
+
+#[derive(DeriveIden)]
+pub enum TorrentSwarmHistory {
+    Table,
+    TorrentId,
+    RecordedAt,
+    Seeders,
+    Leechers,
 }
 
 #[derive(DeriveIden)]
@@ -34,6 +67,18 @@ pub enum Stats {
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220120_000001_create_torrents_table::Migration)]
+        vec![
+            Box::new(m20220120_000001_create_torrents_table::Migration),
+            Box::new(m20261018_000001_add_torrents_search_vector::Migration),
+            Box::new(m20261018_000002_add_torrents_category::Migration),
+            Box::new(m20261018_000003_add_torrents_release::Migration),
+            Box::new(m20261018_000004_convert_torrents_files_to_jsonb::Migration),
+            Box::new(m20261018_000005_store_torrents_size_in_bytes::Migration),
+            Box::new(m20261018_000006_create_torrent_metadata_table::Migration),
+            Box::new(m20261018_000007_seed_stats_counters::Migration),
+            Box::new(m20261018_000008_create_torrent_swarm_history_table::Migration),
+            Box::new(m20261018_000009_add_torrents_leased_until::Migration),
+            Box::new(m20261018_000010_add_torrents_metadata_attempts::Migration),
+        ]
     }
 }
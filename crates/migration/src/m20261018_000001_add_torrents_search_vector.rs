@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Release names use dots, underscores and brackets as separators, which the default
+// text search parser would otherwise keep together as a single host/file token
+const SEARCH_VECTOR: &str =
+    "to_tsvector('simple', regexp_replace(coalesce(name, ''), '[._\\-\\[\\]()+]+', ' ', 'g'))";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Torrents::SearchVector)
+                            .custom(Alias::new("tsvector"))
+                            .extra(format!("GENERATED ALWAYS AS ({}) STORED", SEARCH_VECTOR)),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .if_not_exists()
+                    .col(Torrents::SearchVector)
+                    .table(Torrents::Table)
+                    .name("torrents_search_vector_idx")
+                    .full_text()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                sea_query::Index::drop()
+                    .if_exists()
+                    .table(Torrents::Table)
+                    .name("torrents_search_vector_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::SearchVector)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
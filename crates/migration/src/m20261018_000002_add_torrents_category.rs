@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Mirrors torrent::Model::category_for_name for torrents indexed before the column existed
+const BACKFILL: &str = r"
+UPDATE torrents SET category = CASE
+    WHEN name ~ '(.+?)(S(\d{2})|E(\d{2})|Season|Episode)(.*)' THEN 5000
+    WHEN name LIKE '%1080p%' OR name LIKE '%720p%' THEN 2000
+    WHEN name LIKE '%MP3%' OR name LIKE '%FLAC%' THEN 3000
+    WHEN name LIKE '%PDF%' OR name LIKE '%EPUB%' THEN 7000
+    WHEN name LIKE '%PC%' OR name LIKE '%MAC%' THEN 4000
+    WHEN name LIKE '%XXX%' THEN 6000
+    ELSE 8000
+END
+WHERE name IS NOT NULL
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Torrents::Category)
+                            .integer()
+                            .not_null()
+                            .default(8000),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(BACKFILL)
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .if_not_exists()
+                    .col(Torrents::Category)
+                    .table(Torrents::Table)
+                    .name("torrents_category_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                sea_query::Index::drop()
+                    .if_exists()
+                    .table(Torrents::Table)
+                    .name("torrents_category_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::Category)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Existing torrents are parsed by the release backfill task once the column exists
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Torrents::Release).json_binary())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::Release)
+                    .to_owned(),
+            )
+            .await
+    }
+}
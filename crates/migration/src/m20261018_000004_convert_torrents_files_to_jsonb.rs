@@ -0,0 +1,133 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Lengths of files indexed as plain paths were never stored, so they start out as 0
+const TO_JSONB: &str = r"
+UPDATE torrents SET files_json = coalesce((
+    SELECT jsonb_agg(jsonb_build_object(
+        'path', path,
+        'length', 0,
+        'extension', lower(substring(path from '\.([A-Za-z0-9]{1,8})$'))
+    ))
+    FROM unnest(files) AS path
+), '[]'::jsonb)
+";
+
+// Paths stored since the conversion can be longer than the old column allows
+const TO_ARRAY: &str = r"
+UPDATE torrents SET files_array = ARRAY(
+    SELECT left(file->>'path', 255) FROM jsonb_array_elements(files) AS file
+)
+";
+
+// jsonb_path_ops only supports @>, which is all searching by extension needs
+const FILES_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS torrents_files_idx ON torrents USING GIN (files jsonb_path_ops)";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let files_json = Alias::new("files_json");
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column(
+                        ColumnDef::new(files_json.clone())
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(TO_JSONB)
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::Files)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .rename_column(files_json, Torrents::Files)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(FILES_INDEX)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let files_array = Alias::new("files_array");
+
+        manager
+            .drop_index(
+                sea_query::Index::drop()
+                    .if_exists()
+                    .table(Torrents::Table)
+                    .name("torrents_files_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column(
+                        ColumnDef::new(files_array.clone())
+                            .array(ColumnType::String(Some(255)))
+                            .not_null()
+                            .default(Expr::cust("'{}'")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(TO_ARRAY)
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::Files)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .rename_column(files_array, Torrents::Files)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const TO_BYTES: &str = "UPDATE torrents SET size = size * 1000000";
+
+const TO_MEGABYTES: &str = "UPDATE torrents SET size = least(size / 1000000, 2147483647)";
+
+// Sizes were stored as truncated megabytes. Torrents whose files carry their lengths get
+// the exact total back, the rest keep the megabyte approximation converted to bytes.
+const EXACT_SIZES: &str = r"
+UPDATE torrents SET size = files_size.total
+FROM (
+    SELECT id, sum((file->>'length')::bigint) AS total
+    FROM torrents, jsonb_array_elements(files) AS file
+    GROUP BY id
+) AS files_size
+WHERE torrents.id = files_size.id AND files_size.total > 0
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .modify_column(ColumnDef::new(Torrents::Size).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        db.execute_unprepared(TO_BYTES).await?;
+        db.execute_unprepared(EXACT_SIZES).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(TO_MEGABYTES)
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .modify_column(ColumnDef::new(Torrents::Size).integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
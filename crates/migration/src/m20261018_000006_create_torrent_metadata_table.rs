@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{TorrentMetadata, Torrents};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TorrentMetadata::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TorrentMetadata::TorrentId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TorrentMetadata::Info).binary().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("torrent_metadata_torrent_id_fkey")
+                            .from(TorrentMetadata::Table, TorrentMetadata::TorrentId)
+                            .to(Torrents::Table, Torrents::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(TorrentMetadata::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
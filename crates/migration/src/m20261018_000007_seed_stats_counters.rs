@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Stats;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const DEDUPLICATE: &str = r"
+DELETE FROM stats a USING stats b WHERE a.name = b.name AND a.id > b.id
+";
+
+// Counters are only adjusted from here on, so they start from an exact count
+const SEED: &str = r"
+INSERT INTO stats (name, value, last_updated) VALUES
+    ('total_torrents', (SELECT count(*) FROM torrents), now()),
+    ('scraped_torrents', (
+        SELECT count(*) FROM torrents
+        WHERE last_scrape IS NOT NULL AND last_tracker_scrape IS NOT NULL
+    ), now()),
+    ('queue_torrent_info', (SELECT count(*) FROM torrents WHERE last_scrape IS NULL), now()),
+    ('queue_torrent_trackers', 0, now()),
+    ('stale_torrents', (SELECT count(*) FROM torrents WHERE last_stale IS NOT NULL), now())
+ON CONFLICT (name) DO UPDATE SET value = EXCLUDED.value, last_updated = EXCLUDED.last_updated
+";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(DEDUPLICATE).await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .if_not_exists()
+                    .col(Stats::Name)
+                    .table(Stats::Table)
+                    .name("stats_name_key")
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        db.execute_unprepared(SEED).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                sea_query::Index::drop()
+                    .if_exists()
+                    .table(Stats::Table)
+                    .name("stats_name_key")
+                    .to_owned(),
+            )
+            .await
+    }
+}
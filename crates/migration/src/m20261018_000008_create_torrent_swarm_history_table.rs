@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{TorrentSwarmHistory, Torrents};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TorrentSwarmHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TorrentSwarmHistory::TorrentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TorrentSwarmHistory::RecordedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TorrentSwarmHistory::Seeders)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TorrentSwarmHistory::Leechers)
+                            .integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(TorrentSwarmHistory::TorrentId)
+                            .col(TorrentSwarmHistory::RecordedAt),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("torrent_swarm_history_torrent_id_fkey")
+                            .from(TorrentSwarmHistory::Table, TorrentSwarmHistory::TorrentId)
+                            .to(Torrents::Table, Torrents::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Seed every scraped torrent with its current counts so trends have a starting point
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r"
+                INSERT INTO torrent_swarm_history (torrent_id, recorded_at, seeders, leechers)
+                SELECT id, last_tracker_scrape, seeders, leechers FROM torrents
+                WHERE last_tracker_scrape IS NOT NULL
+                ON CONFLICT DO NOTHING
+                ",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(TorrentSwarmHistory::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Set while a worker has claimed the torrent from the info or tracker queue
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Torrents::LeasedUntil).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .if_not_exists()
+                    .col(Torrents::LeasedUntil)
+                    .table(Torrents::Table)
+                    .name("torrents_leased_until_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                sea_query::Index::drop()
+                    .if_exists()
+                    .table(Torrents::Table)
+                    .name("torrents_leased_until_idx")
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::LeasedUntil)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use crate::Torrents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Failed metadata fetches of a torrent in the info queue, and when it may be tried again
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(Torrents::MetadataAttempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column_if_not_exists(ColumnDef::new(Torrents::NextAttemptAt).date_time())
+                    .add_column_if_not_exists(ColumnDef::new(Torrents::MetadataError).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Torrents::Table)
+                    .drop_column(Torrents::MetadataAttempts)
+                    .drop_column(Torrents::NextAttemptAt)
+                    .drop_column(Torrents::MetadataError)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
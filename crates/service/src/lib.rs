@@ -1,6 +1,8 @@
+mod magnet;
 mod mutation;
 mod query;
 
+pub use magnet::*;
 pub use mutation::*;
 pub use query::*;
 
@@ -0,0 +1,195 @@
+use ::oxidized_entity::info_hash::InfoHash;
+use ::oxidized_entity::torrent::{self, Trackers};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+pub struct Magnet;
+
+// Trackers beyond this rarely help and make links unwieldy to paste
+const MAX_TRACKERS: usize = 10;
+
+// Everything but unreserved characters (RFC 3986) is escaped in magnet parameters
+const PARAM: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// Escapes a value for a magnet or any other URL query parameter
+pub fn encode_param(value: &str) -> String {
+    utf8_percent_encode(value, PARAM).to_string()
+}
+
+impl Magnet {
+    // Announce URLs of the trackers that answered the last scrape, best seeded first
+    pub fn best_trackers(trackers: &Trackers) -> Vec<String> {
+        let mut trackers = trackers.0.iter().collect::<Vec<_>>();
+
+        trackers.sort_by(|a, b| b.seeders.cmp(&a.seeders).then(b.leechers.cmp(&a.leechers)));
+
+        let mut urls: Vec<String> = vec![];
+
+        for tracker in trackers {
+            if !urls.contains(&tracker.url) {
+                urls.push(tracker.url.clone());
+            }
+        }
+
+        urls
+    }
+
+    // magnet:?xt=urn:btih:HASH (urn:btmh for v2) with the display name, the exact length and
+    // the best trackers, if known
+    pub fn uri(torrent: &torrent::Model) -> String {
+        let topic = match InfoHash::parse(&torrent.info_hash) {
+            Ok(info_hash) => info_hash.magnet_topic(),
+            Err(_) => format!("urn:btih:{}", torrent.info_hash.to_uppercase()),
+        };
+
+        let mut uri = format!("magnet:?xt={}", topic);
+
+        if let Some(name) = torrent.name.as_deref().filter(|name| !name.is_empty()) {
+            uri.push_str("&dn=");
+            uri.push_str(&encode_param(name));
+        }
+
+        if torrent.size > 0 {
+            uri.push_str(&format!("&xl={}", torrent.size));
+        }
+
+        for tracker in Magnet::best_trackers(&torrent.trackers)
+            .iter()
+            .take(MAX_TRACKERS)
+        {
+            uri.push_str("&tr=");
+            uri.push_str(&encode_param(tracker));
+        }
+
+        uri
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::oxidized_entity::torrent::{Files, Tracker};
+    use chrono::NaiveDateTime;
+
+    const HASH: &str = "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A";
+
+    fn tracker(url: &str, seeders: i32, leechers: i32) -> Tracker {
+        Tracker {
+            url: url.to_string(),
+            seeders,
+            leechers,
+            last_scrape: NaiveDateTime::default(),
+        }
+    }
+
+    fn torrent(name: Option<&str>, size: i64, trackers: Vec<Tracker>) -> torrent::Model {
+        torrent::Model {
+            id: 1,
+            name: name.map(str::to_string),
+            info_hash: HASH.to_string(),
+            size,
+            files: Files(vec![]),
+            added_at: NaiveDateTime::default(),
+            seeders: 0,
+            leechers: 0,
+            trackers: Trackers(trackers),
+            category: 0,
+            release: None,
+            last_scrape: None,
+            last_tracker_scrape: None,
+            last_stale: None,
+            leased_until: None,
+            metadata_attempts: 0,
+            next_attempt_at: None,
+            metadata_error: None,
+        }
+    }
+
+    #[test]
+    fn builds_bare_uri() {
+        let uri = Magnet::uri(&torrent(None, 0, vec![]));
+
+        assert_eq!(uri, format!("magnet:?xt=urn:btih:{}", HASH));
+    }
+
+    #[test]
+    fn builds_v2_uri() {
+        let mut torrent = torrent(None, 0, vec![]);
+        torrent.info_hash =
+            "181226b2d004b8f1ba9db868228a9768587c9f6d8ce606e85b015e9dca415523".to_string();
+
+        assert_eq!(
+            Magnet::uri(&torrent),
+            "magnet:?xt=urn:btmh:1220181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA415523"
+        );
+    }
+
+    #[test]
+    fn encodes_name_size_and_trackers() {
+        let torrent = torrent(
+            Some("Tom & Jerry? (1940) 100%"),
+            4_000_000_000,
+            vec![tracker("http://tracker.example/announce?key=a b&x=1", 5, 0)],
+        );
+
+        assert_eq!(
+            Magnet::uri(&torrent),
+            format!(
+                "magnet:?xt=urn:btih:{}\
+                 &dn=Tom%20%26%20Jerry%3F%20%281940%29%20100%25\
+                 &xl=4000000000\
+                 &tr=http%3A%2F%2Ftracker.example%2Fannounce%3Fkey%3Da%20b%26x%3D1",
+                HASH
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_unicode_and_unreserved_characters() {
+        let uri = Magnet::uri(&torrent(Some("Amélie.2001-x264_~"), 0, vec![]));
+
+        assert!(uri.ends_with("&dn=Am%C3%A9lie.2001-x264_~"), "{}", uri);
+    }
+
+    #[test]
+    fn skips_empty_names() {
+        let uri = Magnet::uri(&torrent(Some(""), 0, vec![]));
+
+        assert!(!uri.contains("&dn="), "{}", uri);
+    }
+
+    #[test]
+    fn orders_trackers_by_seeders_then_leechers() {
+        let trackers = Trackers(vec![
+            tracker("udp://c", 1, 50),
+            tracker("udp://a", 10, 0),
+            tracker("udp://b", 1, 60),
+            // the same tracker scraped twice only appears once, where it did best
+            tracker("udp://c", 0, 0),
+            tracker("udp://d", 0, 0),
+        ]);
+
+        assert_eq!(
+            Magnet::best_trackers(&trackers),
+            ["udp://a", "udp://b", "udp://c", "udp://d"]
+        );
+    }
+
+    #[test]
+    fn limits_trackers_in_uri() {
+        let trackers = (0..MAX_TRACKERS as i32 + 5)
+            .map(|seeders| tracker(&format!("udp://t{}", seeders), seeders, 0))
+            .collect();
+
+        let uri = Magnet::uri(&torrent(None, 0, trackers));
+
+        assert_eq!(uri.matches("&tr=").count(), MAX_TRACKERS);
+        // the best seeded are kept
+        assert!(uri.contains("&tr=udp%3A%2F%2Ft14"), "{}", uri);
+        assert!(!uri.contains("&tr=udp%3A%2F%2Ft4&"), "{}", uri);
+        assert!(!uri.ends_with("&tr=udp%3A%2F%2Ft4"), "{}", uri);
+    }
+}
@@ -1,52 +1,68 @@
-use ::oxidized_entity::stats::Entity as Stats;
-use ::oxidized_entity::torrent::{self, Entity as Torrent, Tracker, Trackers};
+use ::oxidized_entity::metadata::{self, Entity as Metadata};
+use ::oxidized_entity::release::Release;
+use ::oxidized_entity::stats::{self, Entity as Stats, StatType};
+use ::oxidized_entity::swarm_history::{self, Entity as SwarmHistory};
+use ::oxidized_entity::torrent::{self, Entity as Torrent, File, Files, Tracker, Trackers};
 use chrono::Utc;
-use sea_orm::{sea_query::Expr, *};
+use sea_orm::{
+    prelude::DateTime,
+    sea_query::{Expr, SimpleExpr},
+    *,
+};
+use std::collections::HashMap;
 
 pub struct Mutation;
 
+// How long a worker holds a torrent it claimed from a queue. Claims that are not finished by
+// then, because the worker died or fell behind, go back to the queue.
+pub const QUEUE_LEASE_MINUTES: i64 = 10;
+
+// A torrent coming from outside the spider, such as a dump of another instance. Torrents
+// without a name still need their info fetched.
+pub struct NewTorrent {
+    pub info_hash: String,
+    pub name: Option<String>,
+    pub size: i64,
+    pub files: Vec<File>,
+    pub added_at: Option<DateTime>,
+    // bencoded info dictionary, already checked against the info hash
+    pub info: Option<Vec<u8>>,
+}
+
+// id, last_scrape, last_tracker_scrape and last_stale of a torrent
+type TorrentState = (i32, Option<DateTime>, Option<DateTime>, Option<DateTime>);
+
 impl Mutation {
-    pub async fn update_stat(
-        db: &DbConn,
-        name: &str,
-        action: &str,
-        value: Option<i32>,
+    // Adds `delta` to a counter, on the same connection (usually the transaction) as the
+    // change it accounts for so the two cannot drift apart
+    pub async fn adjust_stat<C: ConnectionTrait>(
+        db: &C,
+        stat: StatType,
+        delta: i64,
     ) -> Result<(), DbErr> {
-        // TODO: Surely sea-orm has a better way to do this?
-        match action {
-            "inc" => {
-                let sql = format!(
-                    "UPDATE {} SET value = value + {} WHERE name = '{}'",
-                    Stats.table_name(),
-                    value.unwrap_or(1),
-                    name
-                );
-
-                db.execute_unprepared(&sql)
-                    .await
-                    .map_err(|e| DbErr::from(e))
-            }
-            "dec" => {
-                let sql = format!(
-                    "UPDATE {} SET value = value - {} WHERE name = '{}'",
-                    Stats.table_name(),
-                    value.unwrap_or(1),
-                    name
-                );
-
-                db.execute_unprepared(&sql)
-                    .await
-                    .map_err(|e| DbErr::from(e))
-            }
-            _ => {
-                return Err(DbErr::Custom("Invalid action.".to_owned()));
-            }
-        }?;
+        if delta == 0 {
+            return Ok(());
+        }
+
+        Stats::update_many()
+            .col_expr(
+                stats::Column::Value,
+                Expr::col(stats::Column::Value).add(delta),
+            )
+            .col_expr(
+                stats::Column::LastUpdated,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            // the column is plain text, comparing it as the enum would cast to a missing type
+            .filter(Expr::col(stats::Column::Name).eq(stat.to_value()))
+            .exec(db)
+            .await?;
 
         Ok(())
     }
 
-    pub async fn create_torrents(db: &DbConn, info_hashes: Vec<String>) -> Result<(), DbErr> {
+    // Inserts the info hashes that are not indexed yet and returns how many that were
+    pub async fn create_torrents(db: &DbConn, info_hashes: Vec<String>) -> Result<u64, DbErr> {
         let mut torrents = Vec::new();
         // bulk insert
         for info_hash in info_hashes {
@@ -54,7 +70,7 @@ impl Mutation {
                 name: Set(None),
                 info_hash: Set(info_hash),
                 size: Set(0),
-                files: Set(Vec::new()),
+                files: Set(Files::default()),
                 seeders: Set(0),
                 leechers: Set(0),
                 added_at: Set(Utc::now().naive_utc()),
@@ -65,49 +81,162 @@ impl Mutation {
             torrents.push(torrent);
         }
 
-        Torrent::insert_many(torrents)
-            .exec(db)
-            .await
-            .map_err(|e| DbErr::from(e))?;
+        if torrents.is_empty() {
+            return Ok(0);
+        }
 
-        Mutation::update_stat(db, "total_torrents", "inc", None).await?;
-        Mutation::update_stat(db, "queue_torrent_info", "inc", None).await?;
+        let txn = db.begin().await?;
 
-        Ok(())
+        let inserted = Torrent::insert_many(torrents)
+            .on_conflict(
+                sea_query::OnConflict::column(torrent::Column::InfoHash)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+
+        Mutation::adjust_stat(&txn, StatType::TotalTorrents, inserted as i64).await?;
+        Mutation::adjust_stat(&txn, StatType::QueueInfo, inserted as i64).await?;
+
+        txn.commit().await?;
+
+        Ok(inserted)
+    }
+
+    // Inserts the torrents that are not indexed yet and returns how many that were. Named
+    // torrents count as having their info already, the rest join the info queue. Info
+    // dictionaries are stored for any torrent that does not have one yet.
+    pub async fn import_torrents(db: &DbConn, torrents: Vec<NewTorrent>) -> Result<u64, DbErr> {
+        let now = Utc::now().naive_utc();
+        let (mut named, mut unnamed) = (vec![], vec![]);
+        let mut infos = HashMap::new();
+
+        for torrent in torrents {
+            if let Some(info) = torrent.info {
+                infos.insert(torrent.info_hash.clone(), info);
+            }
+
+            let mut model = torrent::ActiveModel {
+                info_hash: Set(torrent.info_hash),
+                size: Set(torrent.size),
+                files: Set(Files(torrent.files)),
+                seeders: Set(0),
+                leechers: Set(0),
+                added_at: Set(torrent.added_at.unwrap_or(now)),
+                trackers: Set(Trackers(Vec::new())),
+                ..Default::default()
+            };
+
+            match torrent.name {
+                Some(name) => {
+                    let release = Release::parse(&name);
+
+                    model.category = Set(release.category(&name));
+                    model.release = Set(Some(release));
+                    model.name = Set(Some(name));
+                    model.last_scrape = Set(Some(now));
+
+                    named.push(model);
+                }
+                None => {
+                    model.name = Set(None);
+
+                    unnamed.push(model);
+                }
+            }
+        }
+
+        let txn = db.begin().await?;
+        let mut inserted = 0;
+
+        for (models, queued) in [(named, false), (unnamed, true)] {
+            if models.is_empty() {
+                continue;
+            }
+
+            let count = Torrent::insert_many(models)
+                .on_conflict(
+                    sea_query::OnConflict::column(torrent::Column::InfoHash)
+                        .do_nothing()
+                        .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await?;
+
+            if queued {
+                Mutation::adjust_stat(&txn, StatType::QueueInfo, count as i64).await?;
+            }
+
+            inserted += count;
+        }
+
+        Mutation::adjust_stat(&txn, StatType::TotalTorrents, inserted as i64).await?;
+
+        if !infos.is_empty() {
+            let ids: Vec<(i32, String)> = Torrent::find()
+                .select_only()
+                .column(torrent::Column::Id)
+                .column(torrent::Column::InfoHash)
+                .filter(torrent::Column::InfoHash.is_in(infos.keys().cloned()))
+                .into_tuple()
+                .all(&txn)
+                .await?;
+
+            let models = ids.into_iter().filter_map(|(id, info_hash)| {
+                infos.remove(&info_hash).map(|info| metadata::ActiveModel {
+                    torrent_id: Set(id),
+                    info: Set(info),
+                })
+            });
+
+            Metadata::insert_many(models)
+                .on_conflict(
+                    sea_query::OnConflict::column(metadata::Column::TorrentId)
+                        .do_nothing()
+                        .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(inserted)
     }
 
     pub async fn delete_torrents(db: &DbConn, ids: Vec<i32>) -> Result<(), DbErr> {
-        let res = Torrent::delete_many()
-            .filter(Expr::col(torrent::Column::Id).is_in(ids))
-            .exec(db)
-            .await
-            .map_err(|e| DbErr::from(e))?;
+        let txn = db.begin().await?;
 
-        Mutation::update_stat(db, "total_torrents", "dec", Some(res.rows_affected as i32)).await?;
+        Mutation::delete_torrents_where(&txn, torrent::Column::Id.is_in(ids)).await?;
 
-        Ok(())
+        txn.commit().await
     }
 
     pub async fn create_torrent(
         db: &DbConn,
         info_hash: String,
     ) -> Result<torrent::ActiveModel, DbErr> {
+        let txn = db.begin().await?;
+
         let torrent = torrent::ActiveModel {
             name: Set(None),
             info_hash: Set(info_hash),
             size: Set(0),
-            files: Set(Vec::new()),
+            files: Set(Files::default()),
             seeders: Set(0),
             leechers: Set(0),
             added_at: Set(Utc::now().naive_utc()),
             trackers: Set(Trackers(Vec::new())),
             ..Default::default()
         }
-        .save(db)
+        .save(&txn)
         .await?;
 
-        Mutation::update_stat(db, "total_torrents", "inc", None).await?;
-        Mutation::update_stat(db, "queue_torrent_info", "inc", None).await?;
+        Mutation::adjust_stat(&txn, StatType::TotalTorrents, 1).await?;
+        Mutation::adjust_stat(&txn, StatType::QueueInfo, 1).await?;
+
+        txn.commit().await?;
 
         Ok(torrent)
     }
@@ -116,14 +245,20 @@ impl Mutation {
         db: &DbConn,
         info_hash: String,
         name: String,
-        size: i32,
-        files: Vec<String>,
+        size: i64,
+        files: Vec<File>,
     ) -> Result<torrent::ActiveModel, DbErr> {
+        let release = Release::parse(&name);
+
+        let txn = db.begin().await?;
+
         let torrent = torrent::ActiveModel {
+            category: Set(release.category(&name)),
+            release: Set(Some(release)),
             name: Set(Some(name)),
             info_hash: Set(info_hash),
             size: Set(size),
-            files: Set(files),
+            files: Set(Files(files)),
             seeders: Set(0),
             leechers: Set(0),
             added_at: Set(Utc::now().naive_utc()),
@@ -131,27 +266,207 @@ impl Mutation {
             last_scrape: Set(Some(Utc::now().naive_utc())),
             ..Default::default()
         }
-        .save(db)
+        .save(&txn)
         .await?;
 
-        Mutation::update_stat(db, "total_torrents", "inc", None).await?;
-        Mutation::update_stat(db, "queue_torrent_trackers", "inc", None).await?;
+        Mutation::adjust_stat(&txn, StatType::TotalTorrents, 1).await?;
+
+        txn.commit().await?;
 
         Ok(torrent)
     }
 
+    // Adds a torrent whose info dictionary is already known, so it skips the info queue.
+    // Returns None when the info hash is indexed already.
+    pub async fn create_torrent_with_metadata(
+        db: &DbConn,
+        info_hash: String,
+        name: String,
+        size: i64,
+        files: Vec<File>,
+        info: Vec<u8>,
+    ) -> Result<Option<i32>, DbErr> {
+        let release = Release::parse(&name);
+
+        let txn = db.begin().await?;
+
+        let inserted = Torrent::insert(torrent::ActiveModel {
+            category: Set(release.category(&name)),
+            release: Set(Some(release)),
+            name: Set(Some(name)),
+            info_hash: Set(info_hash),
+            size: Set(size),
+            files: Set(Files(files)),
+            seeders: Set(0),
+            leechers: Set(0),
+            added_at: Set(Utc::now().naive_utc()),
+            trackers: Set(Trackers(Vec::new())),
+            last_scrape: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .on_conflict(
+            sea_query::OnConflict::column(torrent::Column::InfoHash)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await;
+
+        let id = match inserted {
+            Ok(inserted) => inserted.last_insert_id,
+            Err(DbErr::RecordNotInserted) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Metadata::insert(metadata::ActiveModel {
+            torrent_id: Set(id),
+            info: Set(info),
+        })
+        .exec_without_returning(&txn)
+        .await?;
+
+        Mutation::adjust_stat(&txn, StatType::TotalTorrents, 1).await?;
+
+        txn.commit().await?;
+
+        Ok(Some(id))
+    }
+
+    // Leases up to `limit` torrents waiting for their info and due for an attempt. Rows claimed
+    // by another worker are skipped rather than waited on, so any number of workers can poll
+    // the queue.
+    pub async fn claim_torrent_queue_info(
+        db: &DbConn,
+        limit: u64,
+    ) -> Result<Vec<torrent::Model>, DbErr> {
+        let now = Utc::now().naive_utc();
+        let leased_until = now + chrono::Duration::try_minutes(QUEUE_LEASE_MINUTES).unwrap();
+
+        Torrent::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r"
+                UPDATE torrents SET leased_until = $1
+                WHERE id IN (
+                    SELECT id FROM torrents
+                    WHERE last_scrape IS NULL
+                    AND (leased_until IS NULL OR leased_until < $2)
+                    AND (next_attempt_at IS NULL OR next_attempt_at < $2)
+                    LIMIT $3
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *
+                ",
+                [leased_until.into(), now.into(), (limit as i64).into()],
+            ))
+            .all(db)
+            .await
+    }
+
+    // Leases up to `limit` torrents due for a tracker scrape, those never scraped first
+    pub async fn claim_torrent_queue_trackers(
+        db: &DbConn,
+        limit: u64,
+    ) -> Result<Vec<torrent::Model>, DbErr> {
+        let now = Utc::now().naive_utc();
+        let leased_until = now + chrono::Duration::try_minutes(QUEUE_LEASE_MINUTES).unwrap();
+        let three_days_ago = now - chrono::Duration::try_days(3).unwrap();
+
+        let mut torrents = Torrent::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r"
+                UPDATE torrents SET leased_until = $1
+                WHERE id IN (
+                    SELECT id FROM torrents
+                    WHERE last_scrape IS NOT NULL
+                    AND (last_tracker_scrape IS NULL OR last_tracker_scrape < $3)
+                    AND (leased_until IS NULL OR leased_until < $2)
+                    ORDER BY last_tracker_scrape ASC NULLS FIRST
+                    LIMIT $4
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *
+                ",
+                [
+                    leased_until.into(),
+                    now.into(),
+                    three_days_ago.into(),
+                    (limit as i64).into(),
+                ],
+            ))
+            .all(db)
+            .await?;
+
+        // RETURNING does not keep the order of the subquery
+        torrents.sort_by_key(|torrent| torrent.last_tracker_scrape);
+
+        Ok(torrents)
+    }
+
+    // Gives up claims before their lease runs out, such as work queued when a worker stops
+    pub async fn release_torrents(db: &DbConn, ids: Vec<i32>) -> Result<(), DbErr> {
+        Torrent::update_many()
+            .col_expr(
+                torrent::Column::LeasedUntil,
+                Expr::value(Option::<DateTime>::None),
+            )
+            .filter(torrent::Column::Id.is_in(ids))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    // Records a failed metadata fetch and keeps the torrent out of the info queue until
+    // `next_attempt_at`
+    pub async fn fail_torrent_info(
+        db: &DbConn,
+        id: i32,
+        attempts: i32,
+        next_attempt_at: DateTime,
+        error: String,
+    ) -> Result<(), DbErr> {
+        Torrent::update_many()
+            .col_expr(torrent::Column::MetadataAttempts, Expr::value(attempts))
+            .col_expr(
+                torrent::Column::NextAttemptAt,
+                Expr::value(Some(next_attempt_at)),
+            )
+            .col_expr(torrent::Column::MetadataError, Expr::value(Some(error)))
+            .col_expr(
+                torrent::Column::LeasedUntil,
+                Expr::value(Option::<DateTime>::None),
+            )
+            .filter(torrent::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_torrent_info(
         db: &DbConn,
         id: i32,
         name: String,
-        size: i32,
-        files: Vec<String>,
+        size: i64,
+        files: Vec<File>,
     ) -> Result<torrent::Model, DbErr> {
-        let torrent: torrent::ActiveModel = Torrent::find_by_id(id)
-            .one(db)
+        let txn = db.begin().await?;
+
+        let torrent = Torrent::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
             .await?
-            .ok_or(DbErr::Custom("Cannot find torrent.".to_owned()))
-            .map(Into::into)?;
+            .ok_or(DbErr::Custom("Cannot find torrent.".to_owned()))?;
+
+        // leaving the info queue, and counting as scraped if trackers were already done
+        let was_queued = torrent.last_scrape.is_none();
+        let becomes_scraped = was_queued && torrent.last_tracker_scrape.is_some();
+
+        let torrent: torrent::ActiveModel = torrent.into();
+
+        let release = Release::parse(&name);
 
         let torrent = torrent::ActiveModel {
             id: torrent.id,
@@ -160,35 +475,74 @@ impl Mutation {
             seeders: torrent.seeders,
             leechers: torrent.leechers,
             trackers: torrent.trackers,
+            category: Set(release.category(&name)),
+            release: Set(Some(release)),
             name: Set(Some(name)),
             size: Set(size),
-            files: Set(files),
+            files: Set(Files(files)),
             last_scrape: Set(Some(Utc::now().naive_utc())),
             last_tracker_scrape: torrent.last_tracker_scrape,
             last_stale: torrent.last_stale,
+            leased_until: Set(None),
+            metadata_attempts: torrent.metadata_attempts,
+            next_attempt_at: Set(None),
+            metadata_error: Set(None),
         }
-        .update(db)
+        .update(&txn)
         .await?;
 
-        Mutation::update_stat(db, "queue_torrent_info", "dec", None).await?;
-        Mutation::update_stat(db, "queue_torrent_trackers", "inc", None).await?;
+        if was_queued {
+            Mutation::adjust_stat(&txn, StatType::QueueInfo, -1).await?;
+        }
+
+        if becomes_scraped {
+            Mutation::adjust_stat(&txn, StatType::ScrapedTorrents, 1).await?;
+        }
+
+        txn.commit().await?;
 
         Ok(torrent)
     }
 
+    pub async fn save_torrent_metadata(
+        db: &DbConn,
+        torrent_id: i32,
+        info: Vec<u8>,
+    ) -> Result<(), DbErr> {
+        Metadata::insert(metadata::ActiveModel {
+            torrent_id: Set(torrent_id),
+            info: Set(info),
+        })
+        .on_conflict(
+            sea_query::OnConflict::column(metadata::Column::TorrentId)
+                .update_column(metadata::Column::Info)
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn update_torrent_trackers(
         db: &DbConn,
         id: i32,
         trackers: Vec<Tracker>,
     ) -> Result<torrent::Model, DbErr> {
+        let txn = db.begin().await?;
+
         let torrent = Torrent::find_by_id(id)
-            .one(db)
+            .lock_exclusive()
+            .one(&txn)
             .await?
             .ok_or(DbErr::Custom("Cannot find torrent.".to_owned()))?;
 
         let last_stale_set = torrent.last_stale.is_some();
+        let becomes_scraped =
+            torrent.last_tracker_scrape.is_none() && torrent.last_scrape.is_some();
 
         let torrent: torrent::ActiveModel = torrent.into();
+        let now = Utc::now().naive_utc();
 
         let best_tracker = trackers
             .iter()
@@ -202,10 +556,12 @@ impl Mutation {
             seeders: Set(best_tracker.seeders),
             leechers: Set(best_tracker.leechers),
             name: torrent.name,
+            category: torrent.category,
+            release: torrent.release,
             size: torrent.size,
             files: torrent.files,
             last_scrape: torrent.last_scrape,
-            last_tracker_scrape: Set(Some(Utc::now().naive_utc())),
+            last_tracker_scrape: Set(Some(now)),
             trackers: Set(Trackers(trackers.clone())),
             // if no last_stale and seeders/leechers are 0, then set to datetime
             // if last_stale and seeders/leechers are 0, then keep old last_stale
@@ -214,22 +570,65 @@ impl Mutation {
                 && best_tracker.seeders == 0
                 && best_tracker.leechers == 0
             {
-                Set(Some(Utc::now().naive_utc()))
+                Set(Some(now))
             } else if last_stale_set && best_tracker.seeders == 0 && best_tracker.leechers == 0 {
                 torrent.last_stale
             } else {
                 Set(None)
             },
+            leased_until: Set(None),
+            metadata_attempts: torrent.metadata_attempts,
+            next_attempt_at: torrent.next_attempt_at,
+            metadata_error: torrent.metadata_error,
         }
-        .update(db)
+        .update(&txn)
         .await?;
 
-        Mutation::update_stat(db, "queue_torrent_trackers", "dec", None).await?;
+        SwarmHistory::insert(swarm_history::ActiveModel {
+            torrent_id: Set(torrent.id),
+            recorded_at: Set(now),
+            seeders: Set(torrent.seeders),
+            leechers: Set(torrent.leechers),
+        })
+        .exec_without_returning(&txn)
+        .await?;
+
+        if becomes_scraped {
+            Mutation::adjust_stat(&txn, StatType::ScrapedTorrents, 1).await?;
+        }
+
+        match (last_stale_set, torrent.last_stale.is_some()) {
+            (false, true) => Mutation::adjust_stat(&txn, StatType::Stale, 1).await?,
+            (true, false) => Mutation::adjust_stat(&txn, StatType::Stale, -1).await?,
+            _ => {}
+        }
+
+        txn.commit().await?;
 
         Ok(torrent)
     }
 
+    // Stores the parsed release for torrents named before the parser existed, the category
+    // is recomputed from it as well
+    pub async fn update_torrent_release(db: &DbConn, id: i32, name: &str) -> Result<(), DbErr> {
+        let release = Release::parse(name);
+
+        Torrent::update_many()
+            .col_expr(
+                torrent::Column::Category,
+                Expr::value(release.category(name)),
+            )
+            .col_expr(torrent::Column::Release, Expr::value(release))
+            .filter(torrent::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn mark_stale(db: &DbConn) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+
         let res = Torrent::update_many()
             .col_expr(
                 torrent::Column::LastStale,
@@ -241,49 +640,157 @@ impl Mutation {
                     .and(torrent::Column::Seeders.eq(0))
                     .and(torrent::Column::Leechers.eq(0)),
             )
-            .exec(db)
+            .exec(&txn)
             .await?;
 
-        Mutation::update_stat(db, "stale_torrents", "inc", Some(res.rows_affected as i32)).await?;
+        Mutation::adjust_stat(&txn, StatType::Stale, res.rows_affected as i64).await?;
 
-        Ok(())
+        txn.commit().await
     }
 
-    pub async fn delete_stale(db: &DbConn) -> Result<(), DbErr> {
-        let now = Utc::now().naive_utc();
-        let three_days_ago = now - chrono::Duration::try_days(9).unwrap();
+    // Torrents leave the tracker queue when scraped and join it again three days later, with
+    // no mutation to count them back in. Counting the whole table is too slow for every stats
+    // request, so the counter is refreshed periodically instead.
+    pub async fn recount_tracker_queue(db: &DbConn) -> Result<u64, DbErr> {
+        let three_days_ago = Utc::now().naive_utc() - chrono::Duration::try_days(3).unwrap();
 
-        let res = Torrent::delete_many()
+        let queued = Torrent::find()
             .filter(
-                torrent::Column::LastStale
-                    .is_not_null()
-                    .and(torrent::Column::LastStale.lt(three_days_ago)),
+                (torrent::Column::LastTrackerScrape
+                    .is_null()
+                    .or(torrent::Column::LastTrackerScrape.lt(three_days_ago)))
+                .and(torrent::Column::LastScrape.is_not_null()),
             )
+            .count(db)
+            .await?;
+
+        Stats::update_many()
+            .col_expr(stats::Column::Value, Expr::value(queued as i64))
+            .col_expr(
+                stats::Column::LastUpdated,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(Expr::col(stats::Column::Name).eq(StatType::QueueTrackers.to_value()))
             .exec(db)
             .await?;
 
-        Mutation::update_stat(db, "total_torrents", "dec", Some(res.rows_affected as i32)).await?;
-        Mutation::update_stat(db, "stale_torrents", "dec", Some(res.rows_affected as i32)).await?;
+        Ok(queued)
+    }
 
-        Ok(())
+    // Averages samples older than `keep_samples_days` down to one per day and drops
+    // everything older than `retention_days`. Only whole days are compacted, so a day is
+    // never averaged twice.
+    pub async fn compact_swarm_history(
+        db: &DbConn,
+        keep_samples_days: i64,
+        retention_days: i64,
+    ) -> Result<(), DbErr> {
+        let today = Utc::now().naive_utc().date().and_hms_opt(0, 0, 0).unwrap();
+        let compact_before = today - chrono::Duration::try_days(keep_samples_days).unwrap();
+        let delete_before = today - chrono::Duration::try_days(retention_days).unwrap();
+
+        let txn = db.begin().await?;
+
+        SwarmHistory::delete_many()
+            .filter(swarm_history::Column::RecordedAt.lt(delete_before))
+            .exec(&txn)
+            .await?;
+
+        txn.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r"
+            WITH samples AS (
+                DELETE FROM torrent_swarm_history
+                WHERE recorded_at < $1 AND recorded_at <> date_trunc('day', recorded_at)
+                RETURNING torrent_id, recorded_at, seeders, leechers
+            )
+            INSERT INTO torrent_swarm_history (torrent_id, recorded_at, seeders, leechers)
+            SELECT torrent_id, date_trunc('day', recorded_at), round(avg(seeders)), round(avg(leechers))
+            FROM samples
+            GROUP BY torrent_id, date_trunc('day', recorded_at)
+            ON CONFLICT (torrent_id, recorded_at) DO UPDATE
+            SET seeders = EXCLUDED.seeders, leechers = EXCLUDED.leechers
+            ",
+            [compact_before.into()],
+        ))
+        .await?;
+
+        txn.commit().await
+    }
+
+    pub async fn delete_stale(db: &DbConn) -> Result<(), DbErr> {
+        let now = Utc::now().naive_utc();
+        let three_days_ago = now - chrono::Duration::try_days(9).unwrap();
+
+        let txn = db.begin().await?;
+
+        Mutation::delete_torrents_where(
+            &txn,
+            torrent::Column::LastStale
+                .is_not_null()
+                .and(torrent::Column::LastStale.lt(three_days_ago)),
+        )
+        .await?;
+
+        txn.commit().await
     }
 
     pub async fn delete_torrent(db: &DbConn, id: i32) -> Result<(), DbErr> {
-        let torrent = Torrent::find_by_id(id)
-            .one(db)
-            .await
-            .map_err(|e| DbErr::from(e))?;
+        let txn = db.begin().await?;
 
-        if torrent.is_some() {
-            torrent
-                .unwrap()
-                .delete(db)
-                .await
-                .map_err(|e| DbErr::from(e))?;
+        Mutation::delete_torrents_where(&txn, torrent::Column::Id.eq(id)).await?;
 
-            Mutation::update_stat(db, "total_torrents", "dec", Some(1)).await?;
+        txn.commit().await
+    }
+
+    // Deletes the matching torrents and takes them out of every counter they were part of.
+    // The rows are locked first so they cannot change between counting and deleting.
+    async fn delete_torrents_where<C: ConnectionTrait>(
+        db: &C,
+        condition: SimpleExpr,
+    ) -> Result<(), DbErr> {
+        let rows: Vec<TorrentState> = Torrent::find()
+            .select_only()
+            .column(torrent::Column::Id)
+            .column(torrent::Column::LastScrape)
+            .column(torrent::Column::LastTrackerScrape)
+            .column(torrent::Column::LastStale)
+            .filter(condition)
+            .lock_exclusive()
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        if rows.is_empty() {
+            return Ok(());
         }
 
+        let (mut scraped, mut queued_info, mut stale) = (0, 0, 0);
+
+        for (_, last_scrape, last_tracker_scrape, last_stale) in &rows {
+            if last_scrape.is_some() && last_tracker_scrape.is_some() {
+                scraped += 1;
+            }
+
+            if last_scrape.is_none() {
+                queued_info += 1;
+            }
+
+            if last_stale.is_some() {
+                stale += 1;
+            }
+        }
+
+        let res = Torrent::delete_many()
+            .filter(torrent::Column::Id.is_in(rows.iter().map(|(id, _, _, _)| *id)))
+            .exec(db)
+            .await?;
+
+        Mutation::adjust_stat(db, StatType::TotalTorrents, -(res.rows_affected as i64)).await?;
+        Mutation::adjust_stat(db, StatType::ScrapedTorrents, -scraped).await?;
+        Mutation::adjust_stat(db, StatType::QueueInfo, -queued_info).await?;
+        Mutation::adjust_stat(db, StatType::Stale, -stale).await?;
+
         Ok(())
     }
 }
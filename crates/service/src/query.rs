@@ -1,14 +1,53 @@
-use ::oxidized_entity::{stats, stats::Entity as Stats, torrent, torrent::Entity as Torrent};
-use chrono::Utc;
-use sea_orm::{
-    sea_query::{Expr, Func},
-    *,
+use ::oxidized_entity::{
+    metadata, metadata::Entity as Metadata, stats, stats::Entity as Stats, swarm_history,
+    swarm_history::Entity as SwarmHistory, torrent, torrent::Entity as Torrent,
 };
+use chrono::Utc;
+use sea_orm::{prelude::DateTime, sea_query::Expr, *};
 use serde::Serialize;
-use tokio::try_join;
+use serde_json::json;
+use std::collections::HashSet;
 
 pub struct Query;
 
+#[derive(Default)]
+pub struct SearchQuery {
+    pub query: Option<String>,
+    // case-insensitive POSIX regular expression the name has to match
+    pub pattern: Option<String>,
+    pub categories: Option<Vec<i32>>,
+    // lowercase file extensions without the dot, any of them has to be in the torrent
+    pub extensions: Option<Vec<String>>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+    pub boost_seeders: bool,
+    pub sort: SearchSort,
+}
+
+#[derive(Default)]
+pub enum SearchSort {
+    // full-text rank when there is a query, newest first otherwise
+    #[default]
+    Relevance,
+    // most seeded first, only torrents a tracker has scraped are considered
+    Popular,
+    // seeders gained over the window, only torrents scraped within it are considered
+    Trending(chrono::Duration),
+}
+
+// Seeders now minus seeders at the start of the window: the last sample taken before it, or
+// the first one within it for torrents that are newer than the window
+const SEEDER_GROWTH: &str = r"
+seeders - coalesce(
+    (SELECT h.seeders FROM torrent_swarm_history h
+        WHERE h.torrent_id = torrents.id AND h.recorded_at <= $1
+        ORDER BY h.recorded_at DESC LIMIT 1),
+    (SELECT h.seeders FROM torrent_swarm_history h
+        WHERE h.torrent_id = torrents.id AND h.recorded_at > $2
+        ORDER BY h.recorded_at ASC LIMIT 1),
+    seeders
+)";
+
 #[derive(Serialize)]
 pub struct Queue {
     pub info: u64,
@@ -36,33 +75,96 @@ impl Query {
             .await
     }
 
-    pub async fn search_torrents_by_name(
+    // Ranks torrents by full-text relevance of their name, optionally weighted by seeders so
+    // well-seeded releases come first. Without a query the latest torrents are returned.
+    pub async fn search_torrents(
         db: &DbConn,
-        name: Option<String>,
-        offset: Option<u64>,
-        limit: Option<u64>,
+        search: SearchQuery,
     ) -> Result<Vec<torrent::Model>, DbErr> {
-        Torrent::find()
-            .filter(
-                Expr::expr(Func::lower(Expr::col(torrent::Column::Name))).like(format!(
-                    "%{}%",
-                    name.unwrap_or("".to_string())
-                        .to_lowercase()
-                        .split("%")
-                        .collect::<Vec<&str>>()
-                        .join("\\%")
-                        .split_ascii_whitespace()
-                        .collect::<Vec<&str>>()
-                        .join("%")
-                )),
-            )
-            .offset(offset.unwrap_or(0))
-            .limit(limit.unwrap_or(100))
+        let mut query = Torrent::find().filter(torrent::Column::Name.is_not_null());
+
+        if let Some(pattern) = search.pattern {
+            query = query.filter(Expr::cust_with_values("name ~* $1", [pattern]));
+        }
+
+        if let Some(categories) = search.categories {
+            query = query.filter(torrent::Column::Category.is_in(categories));
+        }
+
+        if let Some(extensions) = search.extensions {
+            let condition =
+                extensions
+                    .into_iter()
+                    .fold(Condition::any(), |condition, extension| {
+                        condition.add(Expr::cust_with_values(
+                            "files @> $1",
+                            [json!([{ "extension": extension }])],
+                        ))
+                    });
+
+            query = query.filter(condition);
+        }
+
+        match search.sort {
+            SearchSort::Relevance => {}
+            SearchSort::Popular => {
+                query = query
+                    .filter(torrent::Column::LastTrackerScrape.is_not_null())
+                    .order_by_desc(torrent::Column::Seeders)
+                    .order_by_desc(torrent::Column::Leechers);
+            }
+            SearchSort::Trending(window) => {
+                let since = Utc::now().naive_utc() - window;
+
+                query = query
+                    .filter(torrent::Column::LastTrackerScrape.gte(since))
+                    .order_by(
+                        Expr::cust_with_values(SEEDER_GROWTH, [since, since]),
+                        Order::Desc,
+                    )
+                    .order_by_desc(torrent::Column::Seeders);
+            }
+        }
+
+        if let Some(tsquery) = search.query.as_deref().and_then(Query::to_tsquery) {
+            let rank = if search.boost_seeders {
+                "ts_rank(search_vector, to_tsquery('simple', $1)) * (1 + ln(1 + greatest(seeders, 0)))"
+            } else {
+                "ts_rank(search_vector, to_tsquery('simple', $1))"
+            };
+
+            query = query
+                .filter(Expr::cust_with_values(
+                    "search_vector @@ to_tsquery('simple', $1)",
+                    [tsquery.clone()],
+                ))
+                .order_by(Expr::cust_with_values(rank, [tsquery]), Order::Desc);
+        }
+
+        query
             .order_by_desc(torrent::Column::AddedAt)
+            .offset(search.offset.unwrap_or(0))
+            .limit(search.limit.unwrap_or(100))
             .all(db)
             .await
     }
 
+    // Turns free text into a prefix-matching tsquery, every term has to match. Anything but
+    // letters and digits is dropped so user input cannot inject tsquery operators.
+    fn to_tsquery(text: &str) -> Option<String> {
+        let terms = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("{}:*", term.to_lowercase()))
+            .collect::<Vec<String>>();
+
+        if terms.is_empty() {
+            return None;
+        }
+
+        Some(terms.join(" & "))
+    }
+
     pub async fn find_torrent_by_info_hash(
         db: &DbConn,
         info_hash: String,
@@ -73,6 +175,57 @@ impl Query {
             .await
     }
 
+    pub async fn find_torrent_metadata(
+        db: &DbConn,
+        torrent_id: i32,
+    ) -> Result<Option<metadata::Model>, DbErr> {
+        Metadata::find_by_id(torrent_id).one(db).await
+    }
+
+    pub async fn find_torrents_metadata(
+        db: &DbConn,
+        torrent_ids: Vec<i32>,
+    ) -> Result<Vec<metadata::Model>, DbErr> {
+        Metadata::find()
+            .filter(metadata::Column::TorrentId.is_in(torrent_ids))
+            .all(db)
+            .await
+    }
+
+    // Which of the torrents have their info dictionary stored, and so a .torrent file
+    pub async fn find_torrents_with_metadata(
+        db: &DbConn,
+        torrent_ids: Vec<i32>,
+    ) -> Result<HashSet<i32>, DbErr> {
+        let ids: Vec<i32> = Metadata::find()
+            .select_only()
+            .column(metadata::Column::TorrentId)
+            .filter(metadata::Column::TorrentId.is_in(torrent_ids))
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        Ok(ids.into_iter().collect())
+    }
+
+    pub async fn find_swarm_history(
+        db: &DbConn,
+        torrent_id: i32,
+        since: Option<DateTime>,
+    ) -> Result<Vec<swarm_history::Model>, DbErr> {
+        let mut query =
+            SwarmHistory::find().filter(swarm_history::Column::TorrentId.eq(torrent_id));
+
+        if let Some(since) = since {
+            query = query.filter(swarm_history::Column::RecordedAt.gte(since));
+        }
+
+        query
+            .order_by_asc(swarm_history::Column::RecordedAt)
+            .all(db)
+            .await
+    }
+
     pub async fn exists_torrent_by_info_hash(db: &DbConn, info_hash: &str) -> Result<bool, DbErr> {
         let count = Torrent::find()
             .filter(torrent::Column::InfoHash.eq(info_hash.to_uppercase()))
@@ -82,7 +235,45 @@ impl Query {
         Ok(count > 0)
     }
 
+    pub async fn find_existing_info_hashes(
+        db: &DbConn,
+        info_hashes: Vec<String>,
+    ) -> Result<Vec<String>, DbErr> {
+        Torrent::find()
+            .select_only()
+            .column(torrent::Column::InfoHash)
+            .filter(torrent::Column::InfoHash.is_in(info_hashes))
+            .into_tuple()
+            .all(db)
+            .await
+    }
+
+    pub async fn find_torrents_without_release(
+        db: &DbConn,
+        limit: u64,
+    ) -> Result<Vec<(i32, String)>, DbErr> {
+        Torrent::find()
+            .select_only()
+            .column(torrent::Column::Id)
+            .column(torrent::Column::Name)
+            .filter(
+                torrent::Column::Name
+                    .is_not_null()
+                    .and(torrent::Column::Release.is_null()),
+            )
+            .order_by_asc(torrent::Column::Id)
+            .limit(limit)
+            .into_tuple()
+            .all(db)
+            .await
+    }
+
+    // Counters are kept exact by the mutations that change them. The tracker queue is the
+    // exception, torrents join it again as their last scrape ages, so it is recounted hourly
+    // by `Mutation::recount_tracker_queue`.
     pub async fn get_stats(db: &DbConn) -> Result<OutputStats, DbErr> {
+        let rows = Stats::find().all(db).await?;
+
         let mut stats = OutputStats {
             torrents: 0,
             scraped: 0,
@@ -93,155 +284,33 @@ impl Query {
             stale: 0,
         };
 
-        let mut rows = Stats::find().all(db).await?;
-
-        let reindex = rows.iter().any(|row| {
-            row.last_updated.and_utc() < (Utc::now() - chrono::Duration::try_seconds(10).unwrap())
-        });
-
-        if rows.is_empty() {
-            for stat in stats::StatType::iter() {
-                let row = stats::ActiveModel {
-                    name: Set(stat),
-                    value: Set(0),
-                    last_updated: Set(Utc::now().naive_utc()),
-                    ..Default::default()
-                };
-
-                let row_model = row.insert(db).await?;
-
-                rows.push(row_model);
-            }
-        }
-
-        if reindex {
-            let raw_stats = Query::get_raw_stats(db).await?;
-
-            for row in rows {
-                let stat = match row.name {
-                    stats::StatType::TotalTorrents => raw_stats.torrents,
-                    stats::StatType::ScrapedTorrents => raw_stats.scraped,
-                    stats::StatType::QueueInfo => raw_stats.queue.info,
-                    stats::StatType::QueueTrackers => raw_stats.queue.trackers,
-                    stats::StatType::Stale => raw_stats.stale,
-                };
-
-                let mut row: stats::ActiveModel = row.into();
-
-                row.value = Set(stat as i32);
-                row.last_updated = Set(Utc::now().naive_utc());
-
-                row.update(db).await?;
-            }
-
-            return Ok(raw_stats);
-        }
-
         for row in rows {
+            let value = row.value.max(0) as u64;
+
             match row.name {
-                stats::StatType::TotalTorrents => stats.torrents = (row.value as u64).max(0),
-                stats::StatType::ScrapedTorrents => stats.scraped = (row.value as u64).max(0),
-                stats::StatType::QueueInfo => stats.queue.info = (row.value as u64).max(0),
-                stats::StatType::QueueTrackers => stats.queue.trackers = (row.value as u64).max(0),
-                stats::StatType::Stale => stats.stale = (row.value as u64).max(0),
+                stats::StatType::TotalTorrents => stats.torrents = value,
+                stats::StatType::ScrapedTorrents => stats.scraped = value,
+                stats::StatType::QueueInfo => stats.queue.info = value,
+                stats::StatType::QueueTrackers => stats.queue.trackers = value,
+                stats::StatType::Stale => stats.stale = value,
             }
         }
 
         Ok(stats)
     }
 
-    pub async fn get_raw_stats(db: &DbConn) -> Result<OutputStats, DbErr> {
-        let now = Utc::now().naive_utc();
-
-        let three_days_ago = now - chrono::Duration::try_days(3).unwrap();
-
-        let (torrents, scraped, queued_info, queued_trackers, stale) = try_join!(
-            Torrent::find().count(db),
-            Torrent::find()
-                .filter(
-                    torrent::Column::LastScrape
-                        .is_not_null()
-                        .and(torrent::Column::LastTrackerScrape.is_not_null())
-                )
-                .count(db),
-            Torrent::find()
-                .filter(torrent::Column::LastScrape.is_null())
-                .count(db),
-            Torrent::find()
-                .filter(
-                    (torrent::Column::LastTrackerScrape
-                        .is_null()
-                        .or(torrent::Column::LastTrackerScrape.lt(three_days_ago)))
-                    .and(torrent::Column::LastScrape.is_not_null())
-                )
-                .count(db),
-            Torrent::find()
-                .filter(torrent::Column::LastStale.is_not_null())
-                .count(db)
-        )?;
-
-        Ok(OutputStats {
-            torrents,
-            scraped,
-            queue: Queue {
-                info: queued_info,
-                trackers: queued_trackers,
-            },
-            stale,
-        })
-    }
-
-    pub async fn find_torrent_queue_info(
-        db: &DbConn,
-        ignore: Option<Vec<i32>>,
-    ) -> Result<Vec<torrent::Model>, DbErr> {
-        let torrents = Torrent::find()
-            .filter(
-                torrent::Column::Id
-                    .is_not_in(ignore.unwrap_or_default())
-                    .and(torrent::Column::LastScrape.is_null()),
-            )
-            .limit(50)
-            .all(db)
-            .await;
-
-        torrents
-    }
-
-    pub async fn find_torrent_queue_trackers(
+    // Keyset pagination over the whole table, for walking it without holding a cursor open
+    pub async fn find_torrents_after(
         db: &DbConn,
-        ignore: Option<Vec<i32>>,
+        after_id: i32,
+        limit: u64,
     ) -> Result<Vec<torrent::Model>, DbErr> {
-        let now = Utc::now().naive_utc();
-        let three_days_ago = now - chrono::Duration::try_days(3).unwrap();
-
-        let mut torrents = Torrent::find()
-            .filter(
-                torrent::Column::Id
-                    .is_not_in(ignore.unwrap_or_default())
-                    .and(
-                        torrent::Column::LastTrackerScrape
-                            .is_null()
-                            .or(torrent::Column::LastTrackerScrape.lt(three_days_ago)),
-                    )
-                    .and(torrent::Column::LastScrape.is_not_null()),
-            )
-            .order_by_asc(torrent::Column::LastTrackerScrape)
-            .limit(50)
+        Torrent::find()
+            .filter(torrent::Column::Id.gt(after_id))
+            .order_by_asc(torrent::Column::Id)
+            .limit(limit)
             .all(db)
-            .await?;
-
-        torrents.sort_by(|a, b| {
-            if a.last_tracker_scrape.is_none() {
-                std::cmp::Ordering::Less
-            } else if b.last_tracker_scrape.is_none() {
-                std::cmp::Ordering::Greater
-            } else {
-                a.last_tracker_scrape.cmp(&b.last_tracker_scrape)
-            }
-        });
-
-        Ok(torrents)
+            .await
     }
 
     pub async fn find_torrents_in_page(
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    pub fn dict<const N: usize>(entries: [(&str, Value); N]) -> Self {
+        Value::Dict(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.as_bytes().to_vec(), value))
+                .collect(),
+        )
+    }
+
+    pub fn bytes(bytes: impl AsRef<[u8]>) -> Self {
+        Value::Bytes(bytes.as_ref().to_vec())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes()
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+
+        self.encode_into(&mut out);
+
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Int(int) => {
+                out.push(b'i');
+                out.extend_from_slice(int.to_string().as_bytes());
+                out.push(b'e');
+            }
+            Value::Bytes(bytes) => {
+                out.extend_from_slice(bytes.len().to_string().as_bytes());
+                out.push(b':');
+                out.extend_from_slice(bytes);
+            }
+            Value::List(list) => {
+                out.push(b'l');
+
+                for value in list {
+                    value.encode_into(out);
+                }
+
+                out.push(b'e');
+            }
+            Value::Dict(dict) => {
+                out.push(b'd');
+
+                // BTreeMap keeps keys sorted, as bencode requires
+                for (key, value) in dict {
+                    Value::Bytes(key.clone()).encode_into(out);
+                    value.encode_into(out);
+                }
+
+                out.push(b'e');
+            }
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> anyhow::Result<Self> {
+        let (value, read) = Value::decode_prefix(buf)?;
+
+        if read != buf.len() {
+            return Err(anyhow::anyhow!(
+                "trailing data after bencode value: {} bytes",
+                buf.len() - read
+            ));
+        }
+
+        Ok(value)
+    }
+
+    // Decodes a single value from the start of `buf`, returning how many bytes it used.
+    // ut_metadata messages append raw piece data after the bencoded header.
+    pub fn decode_prefix(buf: &[u8]) -> anyhow::Result<(Self, usize)> {
+        let mut pos = 0;
+        let value = decode_value(buf, &mut pos, 0)?;
+
+        Ok((value, pos))
+    }
+}
+
+const MAX_DEPTH: usize = 64;
+
+fn decode_value(buf: &[u8], pos: &mut usize, depth: usize) -> anyhow::Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(anyhow::anyhow!("bencode nested too deeply"));
+    }
+
+    match buf.get(*pos) {
+        Some(b'i') => {
+            *pos += 1;
+
+            let end = find(buf, *pos, b'e')?;
+            let int = std::str::from_utf8(&buf[*pos..end])?.parse::<i64>()?;
+
+            *pos = end + 1;
+
+            Ok(Value::Int(int))
+        }
+        Some(b'l') => {
+            *pos += 1;
+
+            let mut list = vec![];
+
+            while buf.get(*pos) != Some(&b'e') {
+                list.push(decode_value(buf, pos, depth + 1)?);
+            }
+
+            *pos += 1;
+
+            Ok(Value::List(list))
+        }
+        Some(b'd') => {
+            *pos += 1;
+
+            let mut dict = BTreeMap::new();
+
+            while buf.get(*pos) != Some(&b'e') {
+                let key = match decode_value(buf, pos, depth + 1)? {
+                    Value::Bytes(key) => key,
+                    other => return Err(anyhow::anyhow!("invalid dict key: {:?}", other)),
+                };
+
+                let value = decode_value(buf, pos, depth + 1)?;
+
+                dict.insert(key, value);
+            }
+
+            *pos += 1;
+
+            Ok(Value::Dict(dict))
+        }
+        Some(b'0'..=b'9') => {
+            let colon = find(buf, *pos, b':')?;
+            let len = std::str::from_utf8(&buf[*pos..colon])?.parse::<usize>()?;
+            let start = colon + 1;
+            let end = start
+                .checked_add(len)
+                .filter(|end| *end <= buf.len())
+                .ok_or_else(|| anyhow::anyhow!("byte string out of bounds"))?;
+
+            *pos = end;
+
+            Ok(Value::Bytes(buf[start..end].to_vec()))
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "unexpected bencode byte: {:?}",
+            *other as char
+        )),
+        None => Err(anyhow::anyhow!("unexpected end of bencode data")),
+    }
+}
+
+fn find(buf: &[u8], from: usize, byte: u8) -> anyhow::Result<usize> {
+    buf[from..]
+        .iter()
+        .position(|b| *b == byte)
+        .map(|i| from + i)
+        .ok_or_else(|| anyhow::anyhow!("unterminated bencode value"))
+}
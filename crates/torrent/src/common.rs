@@ -36,10 +36,7 @@ pub async fn scrape_udp(
     let request = UDPRequest::Scrape(UDPScrapeRequest {
         connection_id,
         transaction_id: TransactionId(0),
-        info_hashes: info_hashes
-            .into_iter()
-            .map(|info_hash| UDPInfoHash(info_hash))
-            .collect(),
+        info_hashes: info_hashes.into_iter().map(UDPInfoHash).collect(),
     });
 
     let response = request_and_response_udp(socket, tracker_addr, request).await?;
@@ -0,0 +1,502 @@
+use metrics::counter;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        Mutex,
+    },
+};
+use tracing::{debug, info, warn};
+
+use crate::bencode::Value;
+
+pub type NodeId = [u8; 20];
+
+static BOOTSTRAP_NODES: &[&str] = &[
+    "router.bittorrent.com:6881",
+    "dht.transmissionbt.com:6881",
+    "router.utorrent.com:6881",
+    "dht.libtorrent.org:25401",
+];
+
+// Upper bound on nodes waiting for a find_node, anything past this is dropped
+const MAX_NODES: usize = 2000;
+// Nodes we hand out in our own find_node and sample_infohashes responses
+const MAX_RECENT_NODES: usize = 8;
+// Info hashes announced to us, which we hand out to other samplers (BEP 51)
+const MAX_SAMPLES: usize = 1000;
+const SAMPLES_PER_RESPONSE: usize = 20;
+// Seconds other nodes should wait before sampling us again
+const SAMPLE_INTERVAL: i64 = 60;
+// Used when a node has not told us its own interval yet
+const DEFAULT_SAMPLE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_SAMPLE_COOLDOWNS: usize = 100_000;
+// Discoveries waiting for the consumer, more are dropped like a lost UDP packet would be
+const DISCOVERY_CHANNEL_SIZE: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+}
+
+// An info hash that a peer told us it is downloading, along with where to reach it
+#[derive(Debug, Clone)]
+pub struct Announcement {
+    pub info_hash: [u8; 20],
+    pub peer: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+pub enum Discovery {
+    Announcement(Announcement),
+    // Info hashes returned by a sample_infohashes query, we only know they exist
+    Samples(Vec<[u8; 20]>),
+}
+
+#[derive(Default)]
+struct State {
+    nodes: VecDeque<Node>,
+    recent: VecDeque<Node>,
+    samples: VecDeque<[u8; 20]>,
+    sample_cooldowns: HashMap<SocketAddr, Instant>,
+}
+
+pub struct Crawler {
+    id: NodeId,
+    socket: Arc<UdpSocket>,
+    state: Arc<Mutex<State>>,
+    bootstrap: Vec<String>,
+    interval: Duration,
+    sampling: bool,
+}
+
+impl Crawler {
+    pub async fn bind(addr: SocketAddr) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+
+        Ok(Self {
+            id: rand::thread_rng().gen(),
+            socket: Arc::new(socket),
+            state: Arc::new(Mutex::new(State::default())),
+            bootstrap: BOOTSTRAP_NODES.iter().map(|x| x.to_string()).collect(),
+            interval: Duration::from_secs(1),
+            sampling: true,
+        })
+    }
+
+    pub fn with_bootstrap(mut self, bootstrap: Vec<String>) -> Self {
+        self.bootstrap = bootstrap;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: bool) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn start(self) -> Receiver<Discovery> {
+        let (tx, rx) = channel(DISCOVERY_CHANNEL_SIZE);
+
+        info!("DHT crawler listening on {:?}", self.socket.local_addr());
+
+        let crawler = Arc::new(self);
+
+        tokio::spawn(crawler.clone().spawn_walker(tx.clone()));
+        tokio::spawn(crawler.spawn_listener(tx));
+
+        rx
+    }
+
+    // Periodically asks every known node for its neighbours while pretending to be close to
+    // them, so they add us to their routing tables and send us get_peers/announce_peer traffic.
+    // With sampling enabled, nodes are also asked for a sample of the info hashes they store.
+    // Both tasks stop once the discovery receiver is dropped.
+    async fn spawn_walker(self: Arc<Self>, tx: Sender<Discovery>) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = tx.closed() => return,
+            }
+
+            let pending = self
+                .state
+                .lock()
+                .await
+                .nodes
+                .drain(..)
+                .collect::<Vec<Node>>();
+
+            if pending.is_empty() {
+                for addr in &self.bootstrap {
+                    let addrs = match addr.to_socket_addrs() {
+                        Ok(addrs) => addrs,
+                        Err(e) => {
+                            debug!("Cannot resolve bootstrap node {}: {}", addr, e);
+                            continue;
+                        }
+                    };
+
+                    for addr in addrs.filter(|addr| addr.is_ipv4()) {
+                        self.send(&query("find_node", &self.id, &random_id()), addr)
+                            .await;
+
+                        if self.sampling && self.start_sample_cooldown(addr).await {
+                            self.send(&query("sample_infohashes", &self.id, &random_id()), addr)
+                                .await;
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            for node in pending {
+                let id = neighbour_id(&node.id, &self.id);
+
+                self.send(&query("find_node", &id, &random_id()), node.addr)
+                    .await;
+
+                if self.sampling && self.start_sample_cooldown(node.addr).await {
+                    self.send(&query("sample_infohashes", &id, &random_id()), node.addr)
+                        .await;
+                }
+            }
+        }
+    }
+
+    async fn spawn_listener(self: Arc<Self>, tx: Sender<Discovery>) {
+        let mut buffer = [0u8; 65536];
+
+        loop {
+            let received = tokio::select! {
+                received = self.socket.recv_from(&mut buffer) => received,
+                _ = tx.closed() => {
+                    debug!("DHT discovery receiver dropped, stopping crawler");
+                    return;
+                }
+            };
+
+            let (read, from) = match received {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!("DHT socket error: {}", e);
+                    continue;
+                }
+            };
+
+            let message = match Value::decode(&buffer[..read]) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+
+            let discovery = match message.get("y").and_then(|y| y.as_bytes()) {
+                Some(b"r") => match message.get("r") {
+                    Some(body) => self.handle_response(body, from).await,
+                    None => None,
+                },
+                Some(b"q") => self.handle_query(&message, from).await,
+                _ => None,
+            };
+
+            if let Some(discovery) = discovery {
+                match tx.try_send(discovery) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        counter!("oxidized_channel_dropped_total", "channel" => "discoveries")
+                            .increment(1);
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        debug!("DHT discovery receiver dropped, stopping crawler");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_response(&self, body: &Value, from: SocketAddr) -> Option<Discovery> {
+        let found = body
+            .get("nodes")
+            .and_then(|n| n.as_bytes())
+            .map(decode_nodes)
+            .unwrap_or_default();
+
+        let mut state = self.state.lock().await;
+
+        for node in found {
+            if state.nodes.len() >= MAX_NODES {
+                break;
+            }
+
+            if node.addr.port() != 0 && node.id != self.id {
+                state.nodes.push_back(node);
+            }
+        }
+
+        let samples = body
+            .get("samples")
+            .and_then(|s| s.as_bytes())?
+            .chunks_exact(20)
+            .filter_map(|chunk| chunk.try_into().ok())
+            .collect::<Vec<[u8; 20]>>();
+
+        if let Some(interval) = body.get("interval").and_then(|i| i.as_int()) {
+            let interval = Duration::from_secs(interval.clamp(0, 6 * 60 * 60) as u64);
+
+            state
+                .sample_cooldowns
+                .insert(from, Instant::now() + interval);
+        }
+
+        if samples.is_empty() {
+            return None;
+        }
+
+        Some(Discovery::Samples(samples))
+    }
+
+    async fn handle_query(&self, message: &Value, from: SocketAddr) -> Option<Discovery> {
+        let transaction = message.get("t").and_then(|t| t.as_bytes())?;
+        let query = message.get("q").and_then(|q| q.as_bytes())?;
+        let args = message.get("a")?;
+        let sender = args.get("id").and_then(|i| to_id(i.as_bytes()))?;
+
+        let mut state = self.state.lock().await;
+
+        let nodes = encode_nodes(state.recent.iter());
+
+        if !state.recent.iter().any(|node| node.addr == from) {
+            if state.recent.len() >= MAX_RECENT_NODES {
+                state.recent.pop_front();
+            }
+
+            state.recent.push_back(Node {
+                id: sender,
+                addr: from,
+            });
+        }
+
+        match query {
+            b"ping" | b"find_node" => {
+                self.send(
+                    &response(
+                        transaction,
+                        Value::dict([
+                            ("id", Value::bytes(neighbour_id(&sender, &self.id))),
+                            ("nodes", Value::Bytes(nodes)),
+                        ]),
+                    ),
+                    from,
+                )
+                .await;
+
+                None
+            }
+            b"get_peers" => {
+                let info_hash = args.get("info_hash").and_then(|i| to_id(i.as_bytes()))?;
+
+                self.send(
+                    &response(
+                        transaction,
+                        Value::dict([
+                            ("id", Value::bytes(neighbour_id(&info_hash, &self.id))),
+                            ("nodes", Value::bytes([])),
+                            ("token", Value::bytes(&info_hash[..4])),
+                        ]),
+                    ),
+                    from,
+                )
+                .await;
+
+                None
+            }
+            b"sample_infohashes" => {
+                let samples = state
+                    .samples
+                    .make_contiguous()
+                    .choose_multiple(&mut rand::thread_rng(), SAMPLES_PER_RESPONSE)
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<u8>>();
+
+                self.send(
+                    &response(
+                        transaction,
+                        Value::dict([
+                            ("id", Value::bytes(neighbour_id(&sender, &self.id))),
+                            ("interval", Value::Int(SAMPLE_INTERVAL)),
+                            ("nodes", Value::Bytes(nodes)),
+                            ("num", Value::Int(state.samples.len() as i64)),
+                            ("samples", Value::Bytes(samples)),
+                        ]),
+                    ),
+                    from,
+                )
+                .await;
+
+                None
+            }
+            b"announce_peer" => {
+                let info_hash = args.get("info_hash").and_then(|i| to_id(i.as_bytes()))?;
+
+                let implied_port = args.get("implied_port").and_then(|p| p.as_int()) == Some(1);
+
+                let port = if implied_port {
+                    from.port()
+                } else {
+                    match args.get("port").and_then(|p| p.as_int()) {
+                        Some(port) if port > 0 && port <= u16::MAX as i64 => port as u16,
+                        _ => return None,
+                    }
+                };
+
+                if !state.samples.contains(&info_hash) {
+                    if state.samples.len() >= MAX_SAMPLES {
+                        state.samples.pop_front();
+                    }
+
+                    state.samples.push_back(info_hash);
+                }
+
+                self.send(
+                    &response(
+                        transaction,
+                        Value::dict([("id", Value::bytes(neighbour_id(&info_hash, &self.id)))]),
+                    ),
+                    from,
+                )
+                .await;
+
+                let mut peer = from;
+                peer.set_port(port);
+
+                Some(Discovery::Announcement(Announcement { info_hash, peer }))
+            }
+            _ => None,
+        }
+    }
+
+    // Returns false while `addr` is still in its sample interval, otherwise starts a new one
+    async fn start_sample_cooldown(&self, addr: SocketAddr) -> bool {
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+
+        if let Some(until) = state.sample_cooldowns.get(&addr) {
+            if *until > now {
+                return false;
+            }
+        }
+
+        if state.sample_cooldowns.len() >= MAX_SAMPLE_COOLDOWNS {
+            state.sample_cooldowns.retain(|_, until| *until > now);
+        }
+
+        state
+            .sample_cooldowns
+            .insert(addr, now + DEFAULT_SAMPLE_COOLDOWN);
+
+        true
+    }
+
+    async fn send(&self, message: &[u8], addr: SocketAddr) {
+        if let Err(e) = self.socket.send_to(message, addr).await {
+            debug!("Cannot send DHT message to {}: {}", addr, e);
+        }
+    }
+}
+
+fn response(transaction: &[u8], body: Value) -> Vec<u8> {
+    Value::dict([
+        ("t", Value::bytes(transaction)),
+        ("y", Value::bytes("r")),
+        ("r", body),
+    ])
+    .encode()
+}
+
+fn query(name: &str, id: &NodeId, target: &NodeId) -> Vec<u8> {
+    Value::dict([
+        ("t", Value::bytes(random_transaction())),
+        ("y", Value::bytes("q")),
+        ("q", Value::bytes(name)),
+        (
+            "a",
+            Value::dict([("id", Value::bytes(id)), ("target", Value::bytes(target))]),
+        ),
+    ])
+    .encode()
+}
+
+// Compact node info: 20 byte id, 4 byte IPv4 address and 2 byte port per node
+pub fn decode_nodes(compact: &[u8]) -> Vec<Node> {
+    compact
+        .chunks_exact(26)
+        .map(|chunk| {
+            let mut id = [0u8; 20];
+            id.copy_from_slice(&chunk[..20]);
+
+            let ip = Ipv4Addr::new(chunk[20], chunk[21], chunk[22], chunk[23]);
+            let port = u16::from_be_bytes([chunk[24], chunk[25]]);
+
+            Node {
+                id,
+                addr: SocketAddr::V4(SocketAddrV4::new(ip, port)),
+            }
+        })
+        .collect()
+}
+
+pub fn encode_nodes<'a>(nodes: impl Iterator<Item = &'a Node>) -> Vec<u8> {
+    let mut compact = vec![];
+
+    for node in nodes {
+        if let SocketAddr::V4(addr) = node.addr {
+            compact.extend_from_slice(&node.id);
+            compact.extend_from_slice(&addr.ip().octets());
+            compact.extend_from_slice(&addr.port().to_be_bytes());
+        }
+    }
+
+    compact
+}
+
+// An id sharing the first 15 bytes with `target`, making us look like a close neighbour
+pub fn neighbour_id(target: &NodeId, id: &NodeId) -> NodeId {
+    let mut neighbour = [0u8; 20];
+
+    neighbour[..15].copy_from_slice(&target[..15]);
+    neighbour[15..].copy_from_slice(&id[15..]);
+
+    neighbour
+}
+
+pub fn random_id() -> NodeId {
+    rand::thread_rng().gen()
+}
+
+fn random_transaction() -> [u8; 2] {
+    rand::thread_rng().gen()
+}
+
+fn to_id(bytes: Option<&[u8]>) -> Option<NodeId> {
+    bytes.and_then(|bytes| bytes.try_into().ok())
+}
@@ -2,12 +2,14 @@ use librqbit::{
     AddTorrent, AddTorrentOptions, AddTorrentResponse, ByteString, Session, SessionOptions,
     TorrentMetaV1Info,
 };
+use metrics::{counter, histogram};
 use std::{
     net::{SocketAddr, ToSocketAddrs},
     path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use tokio::time::error::Elapsed;
 
 pub fn resolve_domain_to_ip(uri: String) -> anyhow::Result<SocketAddr> {
     let ips: Vec<_> = uri.to_socket_addrs()?.collect();
@@ -17,6 +19,7 @@ pub fn resolve_domain_to_ip(uri: String) -> anyhow::Result<SocketAddr> {
 
 pub struct TorrentInfo {
     session: Arc<Session>,
+    timeout: Duration,
 }
 
 impl TorrentInfo {
@@ -28,12 +31,41 @@ impl TorrentInfo {
             },
         )
         .await?;
-        Ok(Self { session })
+        Ok(Self {
+            session,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
     }
 
     pub async fn get_torrent_info(
         &self,
         info_hash: &str,
+    ) -> Result<TorrentMetaV1Info<ByteString>, anyhow::Error> {
+        let start = Instant::now();
+
+        let info = self.fetch_torrent_info(info_hash).await;
+
+        let outcome = match &info {
+            Ok(_) => "success",
+            Err(e) if e.is::<Elapsed>() => "timeout",
+            Err(_) => "error",
+        };
+
+        counter!("oxidized_info_fetch_total", "outcome" => outcome).increment(1);
+        histogram!("oxidized_info_fetch_duration_seconds", "outcome" => outcome)
+            .record(start.elapsed().as_secs_f64());
+
+        info
+    }
+
+    async fn fetch_torrent_info(
+        &self,
+        info_hash: &str,
     ) -> Result<TorrentMetaV1Info<ByteString>, anyhow::Error> {
         let magnet_url = format!("magnet:?xt=urn:btih:{info_hash}");
 
@@ -47,7 +79,7 @@ impl TorrentInfo {
             }),
         );
 
-        let info = tokio::time::timeout(Duration::from_secs(10), info).await??;
+        let info = tokio::time::timeout(self.timeout, info).await??;
 
         let info = match info {
             AddTorrentResponse::ListOnly(res) => res,
@@ -1,5 +1,9 @@
+pub mod bencode;
 pub mod common;
+pub mod dht;
 pub mod info;
+pub mod metadata;
 pub mod nsfw_filter;
 pub mod spider;
+pub mod torrent_file;
 pub mod trackers;
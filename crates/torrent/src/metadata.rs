@@ -0,0 +1,243 @@
+use sha1::{Digest, Sha1};
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::bencode::Value;
+
+const PROTOCOL: &[u8] = b"BitTorrent protocol";
+const EXTENDED_MESSAGE: u8 = 20;
+const EXTENDED_HANDSHAKE: u8 = 0;
+// The id we ask peers to use when sending us ut_metadata messages
+const UT_METADATA: i64 = 1;
+const PIECE_SIZE: usize = 16 * 1024;
+const MAX_METADATA_SIZE: usize = 10 * 1024 * 1024;
+const MAX_MESSAGE_SIZE: usize = PIECE_SIZE + 1024;
+
+#[derive(Debug, Clone)]
+pub struct MetadataFile {
+    pub path: String,
+    pub length: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub name: String,
+    pub files: Vec<MetadataFile>,
+}
+
+impl Metadata {
+    // Parses a bencoded info dictionary, as exchanged through ut_metadata
+    pub fn from_info(info: &[u8]) -> anyhow::Result<Self> {
+        let info = Value::decode(info)?;
+
+        let name = info
+            .get("name.utf-8")
+            .or_else(|| info.get("name"))
+            .and_then(|name| name.as_bytes())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .ok_or_else(|| anyhow::anyhow!("info dict has no name"))?;
+
+        let files = match info.get("files").and_then(|files| files.as_list()) {
+            Some(files) => files
+                .iter()
+                .map(|file| {
+                    let length = file
+                        .get("length")
+                        .and_then(|length| length.as_int())
+                        .ok_or_else(|| anyhow::anyhow!("file has no length"))?;
+
+                    let path = file
+                        .get("path.utf-8")
+                        .or_else(|| file.get("path"))
+                        .and_then(|path| path.as_list())
+                        .ok_or_else(|| anyhow::anyhow!("file has no path"))?
+                        .iter()
+                        .filter_map(|part| part.as_bytes())
+                        .map(|part| String::from_utf8_lossy(part).to_string())
+                        .collect::<Vec<String>>()
+                        .join("/");
+
+                    Ok(MetadataFile { path, length })
+                })
+                .collect::<anyhow::Result<Vec<MetadataFile>>>()?,
+            None => {
+                let length = info
+                    .get("length")
+                    .and_then(|length| length.as_int())
+                    .ok_or_else(|| anyhow::anyhow!("info dict has no length or files"))?;
+
+                vec![MetadataFile {
+                    path: name.clone(),
+                    length,
+                }]
+            }
+        };
+
+        Ok(Self { name, files })
+    }
+}
+
+// Downloads the info dictionary for `info_hash` from a single peer using the extension
+// protocol (BEP 10) and the metadata exchange extension (BEP 9). The returned bytes are
+// verified against the info hash.
+pub async fn fetch_metadata(
+    peer: SocketAddr,
+    info_hash: &[u8; 20],
+    peer_id: &[u8; 20],
+    timeout: Duration,
+) -> anyhow::Result<Vec<u8>> {
+    tokio::time::timeout(timeout, fetch_metadata_inner(peer, info_hash, peer_id)).await?
+}
+
+async fn fetch_metadata_inner(
+    peer: SocketAddr,
+    info_hash: &[u8; 20],
+    peer_id: &[u8; 20],
+) -> anyhow::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(peer).await?;
+
+    let mut handshake = vec![PROTOCOL.len() as u8];
+    handshake.extend_from_slice(PROTOCOL);
+    // reserved bytes, advertising support for the extension protocol
+    handshake.extend_from_slice(&[0, 0, 0, 0, 0, 0x10, 0, 0]);
+    handshake.extend_from_slice(info_hash);
+    handshake.extend_from_slice(peer_id);
+
+    stream.write_all(&handshake).await?;
+
+    let mut response = [0u8; 68];
+    stream.read_exact(&mut response).await?;
+
+    if &response[1..20] != PROTOCOL {
+        return Err(anyhow::anyhow!("invalid handshake from peer"));
+    }
+
+    if response[25] & 0x10 == 0 {
+        return Err(anyhow::anyhow!(
+            "peer does not support the extension protocol"
+        ));
+    }
+
+    if &response[28..48] != info_hash {
+        return Err(anyhow::anyhow!("peer responded with a different info hash"));
+    }
+
+    write_extended(
+        &mut stream,
+        EXTENDED_HANDSHAKE,
+        &Value::dict([("m", Value::dict([("ut_metadata", Value::Int(UT_METADATA))]))]).encode(),
+    )
+    .await?;
+
+    let mut pieces: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+    let mut metadata_size = 0;
+
+    loop {
+        let len = stream.read_u32().await? as usize;
+
+        if len == 0 {
+            // keep-alive
+            continue;
+        }
+
+        if len > MAX_MESSAGE_SIZE {
+            return Err(anyhow::anyhow!(
+                "message from peer too large: {} bytes",
+                len
+            ));
+        }
+
+        let mut message = vec![0u8; len];
+        stream.read_exact(&mut message).await?;
+
+        if message[0] != EXTENDED_MESSAGE || message.len() < 2 {
+            continue;
+        }
+
+        let payload = &message[2..];
+
+        if message[1] == EXTENDED_HANDSHAKE {
+            let (handshake, _) = Value::decode_prefix(payload)?;
+
+            let their_id = handshake
+                .get("m")
+                .and_then(|m| m.get("ut_metadata"))
+                .and_then(|id| id.as_int())
+                .filter(|id| *id > 0 && *id <= u8::MAX as i64)
+                .ok_or_else(|| anyhow::anyhow!("peer does not support ut_metadata"))?
+                as u8;
+
+            metadata_size = handshake
+                .get("metadata_size")
+                .and_then(|size| size.as_int())
+                .filter(|size| *size > 0 && *size as usize <= MAX_METADATA_SIZE)
+                .ok_or_else(|| anyhow::anyhow!("peer sent an invalid metadata_size"))?
+                as usize;
+
+            for piece in 0..metadata_size.div_ceil(PIECE_SIZE) {
+                write_extended(
+                    &mut stream,
+                    their_id,
+                    &Value::dict([
+                        ("msg_type", Value::Int(0)),
+                        ("piece", Value::Int(piece as i64)),
+                    ])
+                    .encode(),
+                )
+                .await?;
+            }
+
+            continue;
+        }
+
+        if message[1] as i64 != UT_METADATA || metadata_size == 0 {
+            continue;
+        }
+
+        let (header, read) = Value::decode_prefix(payload)?;
+
+        match header.get("msg_type").and_then(|t| t.as_int()) {
+            Some(1) => {
+                let piece = header
+                    .get("piece")
+                    .and_then(|p| p.as_int())
+                    .ok_or_else(|| anyhow::anyhow!("metadata piece without index"))?
+                    as usize;
+
+                pieces.insert(piece, payload[read..].to_vec());
+            }
+            Some(2) => return Err(anyhow::anyhow!("peer rejected metadata request")),
+            _ => continue,
+        }
+
+        if pieces.len() == metadata_size.div_ceil(PIECE_SIZE) {
+            let metadata = pieces.into_values().flatten().collect::<Vec<u8>>();
+
+            if metadata.len() != metadata_size {
+                return Err(anyhow::anyhow!("metadata size mismatch"));
+            }
+
+            if Sha1::digest(&metadata).as_slice() != info_hash {
+                return Err(anyhow::anyhow!("metadata hash mismatch"));
+            }
+
+            return Ok(metadata);
+        }
+    }
+}
+
+async fn write_extended(stream: &mut TcpStream, id: u8, payload: &[u8]) -> anyhow::Result<()> {
+    let mut message = Vec::with_capacity(payload.len() + 6);
+
+    message.extend_from_slice(&(payload.len() as u32 + 2).to_be_bytes());
+    message.push(EXTENDED_MESSAGE);
+    message.push(id);
+    message.extend_from_slice(payload);
+
+    stream.write_all(&message).await?;
+
+    Ok(())
+}
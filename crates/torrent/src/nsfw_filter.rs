@@ -57,7 +57,7 @@ impl NSFWFilter {
                 let body = response.text().await.unwrap();
 
                 let words: Vec<String> = body
-                    .split("\n")
+                    .split('\n')
                     .filter(|s| !s.is_empty())
                     .map(|s| s.trim().to_string())
                     .collect();
@@ -92,6 +92,6 @@ impl NSFWFilter {
             }
         }
 
-        return self.words.clone();
+        self.words.clone()
     }
 }
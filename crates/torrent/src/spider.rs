@@ -1,11 +1,31 @@
+use metrics::{counter, gauge};
+use rand::Rng;
 use serde::Deserialize;
-use std::env::current_dir;
-use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    process::Command,
-    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+use std::{
+    collections::HashSet,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{
+    mpsc::{channel, error::TrySendError, Receiver},
+    watch, Mutex, Semaphore,
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    dht::{Crawler, Discovery},
+    metadata::{fetch_metadata, Metadata},
 };
-use tracing::error;
+
+// Metadata fetches running at once, announcements beyond this are dropped
+const MAX_CONCURRENT_FETCHES: usize = 256;
+// Info hashes remembered to avoid fetching the same metadata twice
+const MAX_SEEN: usize = 100_000;
+// Fetched torrents waiting to be saved, fetches hold their permit until theirs is queued
+const TORRENT_CHANNEL_SIZE: usize = 256;
+// Sample batches waiting to be queued, more are dropped
+const SAMPLE_CHANNEL_SIZE: usize = 64;
 
 #[derive(Deserialize)]
 pub struct MagneticoDFile {
@@ -19,52 +39,182 @@ pub struct MagneticoDTorrent {
     #[serde(rename = "infoHash")]
     pub info_hash: String,
     pub files: Vec<MagneticoDFile>,
+    // bencoded info dictionary the torrent was parsed from
+    #[serde(skip)]
+    pub info: Vec<u8>,
 }
 
 pub struct Spider {
-    path: String,
+    addr: SocketAddr,
+    peer_id: [u8; 20],
+    sampling: bool,
+    shutdown: Option<watch::Receiver<bool>>,
 }
 
 impl Spider {
     pub fn new() -> Self {
+        let mut peer_id = [0u8; 20];
+
+        peer_id[..8].copy_from_slice(b"-OX0100-");
+        rand::thread_rng().fill(&mut peer_id[8..]);
+
         Self {
-            path: current_dir()
-                .unwrap()
-                .join("magneticod")
-                .to_str()
-                .unwrap()
-                .to_string(),
+            addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)),
+            peer_id,
+            sampling: true,
+            shutdown: None,
         }
     }
 
-    pub async fn start(&self) -> UnboundedReceiver<MagneticoDTorrent> {
-        let (tx, rx) = unbounded_channel();
-
-        let path = self.path.clone();
-        let mut child = Command::new(path)
-            .arg("--database=stdout://")
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::null())
-            .spawn()
-            .unwrap();
-
-        if let Some(stdout) = child.stdout.take() {
-            let mut lines = BufReader::new(stdout).lines();
-            tokio::spawn(async move {
-                while let Some(line) = lines.next_line().await.unwrap() {
-                    let torrent: MagneticoDTorrent = serde_json::from_str(&line).unwrap();
-
-                    match tx.send(torrent) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("Cannot send torrent to channel: {:?}", e);
-                            break;
+    pub fn with_sampling(mut self, sampling: bool) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    // Stops crawling once `shutdown` turns true, closing both channels after the metadata
+    // fetches in flight are done
+    pub fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    // Returns torrents with their metadata fetched from announcing peers, and batches of
+    // hex info hashes sampled from other nodes (BEP 51) that still need their metadata.
+    // A slow consumer of the torrents stalls the fetches, so new announcements are dropped.
+    pub async fn start(&self) -> (Receiver<MagneticoDTorrent>, Receiver<Vec<String>>) {
+        let (tx, rx) = channel(TORRENT_CHANNEL_SIZE);
+        let (samples_tx, samples_rx) = channel(SAMPLE_CHANNEL_SIZE);
+
+        let crawler = match Crawler::bind(self.addr).await {
+            Ok(crawler) => crawler.with_sampling(self.sampling),
+            Err(e) => {
+                error!("Cannot start DHT crawler: {:?}", e);
+
+                return (rx, samples_rx);
+            }
+        };
+
+        let mut discoveries = crawler.start();
+        let peer_id = self.peer_id;
+        let seen = Arc::new(Mutex::new(HashSet::new()));
+        let fetches = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+        let mut shutdown = self.shutdown.clone();
+
+        tokio::spawn(async move {
+            loop {
+                gauge!("oxidized_channel_depth", "channel" => "discoveries")
+                    .set(discoveries.len() as f64);
+
+                let discovery = tokio::select! {
+                    discovery = discoveries.recv() => discovery,
+                    _ = stopped(&mut shutdown) => {
+                        info!("Stopping DHT spider");
+                        None
+                    }
+                };
+
+                let Some(discovery) = discovery else {
+                    break;
+                };
+
+                let announcement = match discovery {
+                    Discovery::Announcement(announcement) => announcement,
+                    Discovery::Samples(samples) => {
+                        let samples = samples.iter().map(hex::encode).collect();
+
+                        if let Err(TrySendError::Full(_)) = samples_tx.try_send(samples) {
+                            counter!("oxidized_channel_dropped_total", "channel" => "samples")
+                                .increment(1);
                         }
+
+                        continue;
                     }
+                };
+
+                let Ok(permit) = fetches.clone().try_acquire_owned() else {
+                    continue;
+                };
+
+                {
+                    let mut seen = seen.lock().await;
+
+                    // also covers fetches still in flight, failed ones are removed again
+                    if seen.contains(&announcement.info_hash) {
+                        continue;
+                    }
+
+                    if seen.len() >= MAX_SEEN {
+                        seen.clear();
+                    }
+
+                    seen.insert(announcement.info_hash);
                 }
-            });
+
+                let tx = tx.clone();
+                let seen = seen.clone();
+
+                tokio::spawn(async move {
+                    let metadata = fetch_metadata(
+                        announcement.peer,
+                        &announcement.info_hash,
+                        &peer_id,
+                        Duration::from_secs(10),
+                    )
+                    .await
+                    .and_then(|info| Ok((Metadata::from_info(&info)?, info)));
+
+                    let (metadata, info) = match metadata {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            seen.lock().await.remove(&announcement.info_hash);
+
+                            debug!(
+                                "Cannot fetch metadata for {} from {}: {}",
+                                hex::encode(announcement.info_hash),
+                                announcement.peer,
+                                e
+                            );
+                            return;
+                        }
+                    };
+
+                    let torrent = MagneticoDTorrent {
+                        name: metadata.name,
+                        info_hash: hex::encode(announcement.info_hash),
+                        files: metadata
+                            .files
+                            .into_iter()
+                            .map(|file| MagneticoDFile {
+                                size: file.length,
+                                path: file.path,
+                            })
+                            .collect(),
+                        info,
+                    };
+
+                    // the receiver is only dropped on shutdown
+                    let _ = tx.send(torrent).await;
+
+                    drop(permit);
+                });
+            }
+        });
+
+        (rx, samples_rx)
+    }
+}
+
+async fn stopped(shutdown: &mut Option<watch::Receiver<bool>>) {
+    match shutdown {
+        Some(shutdown) => {
+            let _ = shutdown.wait_for(|stop| *stop).await;
         }
+        None => std::future::pending().await,
+    }
+}
 
-        rx
+impl Default for Spider {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,142 @@
+use librqbit::{ByteString, TorrentMetaV1Info};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+
+use crate::bencode::Value;
+
+// Builds a .torrent file around a stored info dictionary. The dictionary is embedded byte
+// for byte, decoding and encoding it again could change the info hash.
+pub fn build(info: &[u8], trackers: &[String]) -> anyhow::Result<Vec<u8>> {
+    if !matches!(Value::decode(info)?, Value::Dict(_)) {
+        return Err(anyhow::anyhow!("info is not a dictionary"));
+    }
+
+    let mut torrent = BTreeMap::new();
+
+    if let Some(announce) = trackers.first() {
+        torrent.insert(b"announce".to_vec(), Value::bytes(announce));
+        torrent.insert(
+            b"announce-list".to_vec(),
+            Value::List(
+                trackers
+                    .iter()
+                    .map(|tracker| Value::List(vec![Value::bytes(tracker)]))
+                    .collect(),
+            ),
+        );
+    }
+
+    torrent.insert(b"created by".to_vec(), Value::bytes("Oxidized"));
+    torrent.insert(b"creation date".to_vec(), Value::Int(now()));
+
+    // "info" sorts after every other key, so it can be appended to the encoded dict
+    let mut file = Value::Dict(torrent).encode();
+
+    file.pop();
+    file.extend_from_slice(b"4:info");
+    file.extend_from_slice(info);
+    file.push(b'e');
+
+    Ok(file)
+}
+
+// Returns the info dictionary of a .torrent file exactly as it was encoded, so it still
+// hashes to the info hash
+pub fn extract_info(file: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if file.first() != Some(&b'd') {
+        return Err(anyhow::anyhow!("torrent file is not a dictionary"));
+    }
+
+    let mut pos = 1;
+
+    while file.get(pos).is_some_and(|byte| *byte != b'e') {
+        let (key, read) = Value::decode_prefix(&file[pos..])?;
+        pos += read;
+
+        let (value, read) = Value::decode_prefix(&file[pos..])?;
+
+        if key.as_bytes() == Some(b"info") {
+            if !matches!(value, Value::Dict(_)) {
+                return Err(anyhow::anyhow!("info is not a dictionary"));
+            }
+
+            return Ok(file[pos..pos + read].to_vec());
+        }
+
+        pos += read;
+    }
+
+    Err(anyhow::anyhow!("torrent file has no info dictionary"))
+}
+
+// The v1 info hash of an info dictionary, as uppercase hex
+pub fn info_hash(info: &[u8]) -> String {
+    hex::encode_upper(Sha1::digest(info))
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Path and length in bytes of every file in an info dictionary
+pub fn files(info: &TorrentMetaV1Info<ByteString>) -> anyhow::Result<Vec<(String, i64)>> {
+    info.iter_filenames_and_lengths()?
+        .map(|(filename, length)| Ok((filename.to_string()?, length as i64)))
+        .collect()
+}
+
+// Encodes an info dictionary parsed by librqbit. Keys librqbit does not know about (such as
+// "private" or "source") are lost when parsing, so the result is only returned when it
+// still hashes to `info_hash`.
+pub fn encode_info(info_hash: &str, info: &TorrentMetaV1Info<ByteString>) -> Option<Vec<u8>> {
+    let mut dict = BTreeMap::new();
+
+    if let Some(name) = &info.name {
+        dict.insert(b"name".to_vec(), Value::bytes(name));
+    }
+
+    dict.insert(b"pieces".to_vec(), Value::bytes(&info.pieces));
+    dict.insert(
+        b"piece length".to_vec(),
+        Value::Int(info.piece_length as i64),
+    );
+
+    if let Some(length) = info.length {
+        dict.insert(b"length".to_vec(), Value::Int(length as i64));
+    }
+
+    if let Some(md5sum) = &info.md5sum {
+        dict.insert(b"md5sum".to_vec(), Value::bytes(md5sum));
+    }
+
+    if let Some(files) = &info.files {
+        dict.insert(
+            b"files".to_vec(),
+            Value::List(
+                files
+                    .iter()
+                    .map(|file| {
+                        Value::dict([
+                            ("length", Value::Int(file.length as i64)),
+                            (
+                                "path",
+                                Value::List(file.path.iter().map(Value::bytes).collect()),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+        );
+    }
+
+    let encoded = Value::Dict(dict).encode();
+
+    if !hex::encode(Sha1::digest(&encoded)).eq_ignore_ascii_case(info_hash) {
+        return None;
+    }
+
+    Some(encoded)
+}
@@ -1,4 +1,5 @@
 use aquatic_udp_protocol::ScrapeResponse as UDPScrapeResponse;
+use metrics::{counter, histogram};
 use oxidized_config::get_config;
 use reqwest::Client;
 use std::{
@@ -54,6 +55,11 @@ impl TorrentTrackers {
         Ok(new)
     }
 
+    // Trackers known from the last refresh, or from the config
+    pub fn tracker_count(&self) -> usize {
+        self.trackers.len()
+    }
+
     pub async fn fetch_socket(&self) -> Result<UdpSocket, anyhow::Error> {
         let peer_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
         let socket = UdpSocket::bind(peer_addr).await?;
@@ -65,12 +71,14 @@ impl TorrentTrackers {
         let config = get_config();
 
         if let Some(trackers) = config.app.trackers {
-            return Ok(trackers
+            self.trackers = trackers
                 .iter()
                 .map(|tracker| TorrentTracker {
                     uri: tracker.clone(),
                 })
-                .collect());
+                .collect();
+
+            return Ok(self.trackers.clone());
         }
 
         if self.last_updated_trackers.elapsed().as_secs() > 60 || self.trackers.is_empty() {
@@ -110,7 +118,7 @@ impl TorrentTrackers {
                 let body = response.text().await.unwrap();
 
                 let trackers: Vec<String> = body
-                    .split("\n")
+                    .split('\n')
                     .filter(|s| !s.is_empty())
                     .map(|s| s.trim().to_string())
                     .collect();
@@ -143,7 +151,7 @@ impl TorrentTrackers {
                                     )
                                     .await;
 
-                                    if let Ok(_) = response {
+                                    if response.is_ok() {
                                         all.push(TorrentTracker {
                                             uri: tracker.clone(),
                                         });
@@ -163,7 +171,7 @@ impl TorrentTrackers {
                             .await;
 
                             // able to fetch from tracker and have a parseable response
-                            if let Ok(_) = response {
+                            if response.is_ok() {
                                 all.push(TorrentTracker {
                                     uri: tracker.clone(),
                                 });
@@ -196,14 +204,23 @@ impl TorrentTrackers {
         let udp_socket = self.fetch_socket().await?;
         let info_hashes: Vec<[u8; 20]> = info_hashes
             .iter()
-            .map(|info_hash_str| {
-                let info_hash_vec = hex::decode(info_hash_str).unwrap();
-                let info_hash: [u8; 20] = info_hash_vec.as_slice().try_into().unwrap();
+            .filter_map(|info_hash_str| {
+                let info_hash = hex::decode(info_hash_str)
+                    .ok()
+                    .and_then(|info_hash_vec| info_hash_vec.as_slice().try_into().ok());
+
+                if info_hash.is_none() {
+                    warn!("Skipping invalid info hash: {}", info_hash_str);
+                }
 
                 info_hash
             })
             .collect();
 
+        if info_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
         let trackers = self.get_trackers().await?;
 
         let mut tracker_responses: Vec<(String, TorrentScrapeResponse)> = vec![];
@@ -211,8 +228,8 @@ impl TorrentTrackers {
 
         for tracker in trackers {
             if let Some((count, time)) = self.timeout_trackers.get(&tracker.uri) {
-                if count > &0 {
-                    if time.elapsed().as_secs()
+                if count > &0
+                    && time.elapsed().as_secs()
                         < match count {
                             3 => 30,
                             4 => 60,
@@ -220,18 +237,22 @@ impl TorrentTrackers {
                             6 => 240,
                             _ => 300,
                         }
-                    {
-                        continue;
-                    }
+                {
+                    continue;
                 }
             }
 
+            let start = Instant::now();
+
             match tracker.uri.split("://").next().unwrap() {
                 "udp" => {
                     let response = self
                         .fetch_from_tracker_udp(&udp_socket, info_hashes.clone(), &tracker)
                         .await;
 
+                    histogram!("oxidized_tracker_scrape_duration_seconds", "tracker" => tracker.uri.clone())
+                        .record(start.elapsed().as_secs_f64());
+
                     if let Ok(response) = response {
                         self.timeout_trackers.remove(&tracker.uri);
 
@@ -258,6 +279,9 @@ impl TorrentTrackers {
                             response.err().unwrap()
                         );
 
+                        counter!("oxidized_tracker_scrape_failures_total", "tracker" => tracker.uri.clone())
+                            .increment(1);
+
                         failed_trackers.push(tracker.uri.clone());
                     }
                 }
@@ -268,6 +292,9 @@ impl TorrentTrackers {
                     )
                     .await;
 
+                    histogram!("oxidized_tracker_scrape_duration_seconds", "tracker" => tracker.uri.clone())
+                        .record(start.elapsed().as_secs_f64());
+
                     if let Ok(response) = response {
                         self.timeout_trackers.remove(&tracker.uri);
 
@@ -291,6 +318,9 @@ impl TorrentTrackers {
                             response.err().unwrap()
                         );
 
+                        counter!("oxidized_tracker_scrape_failures_total", "tracker" => tracker.uri.clone())
+                            .increment(1);
+
                         failed_trackers.push(tracker.uri.clone());
                     }
                 }
@@ -328,11 +358,11 @@ impl TorrentTrackers {
         }
 
         let tracker_addr = tracker_addr.unwrap();
-        let connection_id = connect_udp(&udp_socket, tracker_addr).await?;
+        let connection_id = connect_udp(udp_socket, tracker_addr).await?;
         // let connection_id = tracker.connection_id.unwrap();
 
         let scrape_response =
-            scrape_udp(&udp_socket, tracker_addr, connection_id, info_hashes).await?;
+            scrape_udp(udp_socket, tracker_addr, connection_id, info_hashes).await?;
 
         Ok(scrape_response)
     }
@@ -0,0 +1,120 @@
+use oxidized_torrent::bencode::Value;
+
+// encoded bytes and the value they decode to
+type Case = (&'static [u8], fn() -> Value);
+
+const VALID: &[Case] = &[
+    (b"i0e", || Value::Int(0)),
+    (b"i42e", || Value::Int(42)),
+    (b"i-42e", || Value::Int(-42)),
+    (b"i9223372036854775807e", || Value::Int(i64::MAX)),
+    (b"0:", || Value::bytes("")),
+    (b"4:spam", || Value::bytes("spam")),
+    (b"3:\x00\xff:", || Value::bytes([0x00, 0xff, b':'])),
+    (b"le", || Value::List(vec![])),
+    (b"l4:spami1ee", || {
+        Value::List(vec![Value::bytes("spam"), Value::Int(1)])
+    }),
+    (b"de", || Value::dict([])),
+    (b"d3:cow3:moo4:spaml1:a1:bee", || {
+        Value::dict([
+            ("cow", Value::bytes("moo")),
+            (
+                "spam",
+                Value::List(vec![Value::bytes("a"), Value::bytes("b")]),
+            ),
+        ])
+    }),
+    (b"d1:md11:ut_metadatai1ee13:metadata_sizei31235ee", || {
+        Value::dict([
+            ("m", Value::dict([("ut_metadata", Value::Int(1))])),
+            ("metadata_size", Value::Int(31235)),
+        ])
+    }),
+];
+
+const INVALID: &[(&str, &[u8])] = &[
+    ("empty input", b""),
+    ("unknown type", b"x"),
+    ("unterminated int", b"i42"),
+    ("empty int", b"ie"),
+    ("non numeric int", b"i4x2e"),
+    ("int overflow", b"i9223372036854775808e"),
+    ("negative string length", b"-1:a"),
+    ("truncated string", b"5:spam"),
+    ("string without colon", b"4spam"),
+    ("string length overflow", b"99999999999999999999999:a"),
+    ("unterminated list", b"l4:spam"),
+    ("unterminated dict", b"d3:cow3:moo"),
+    ("dict without value", b"d3:cowe"),
+    ("int dict key", b"di1e3:mooe"),
+    ("list dict key", b"dle3:mooe"),
+    ("trailing data", b"i1ei2e"),
+];
+
+#[test]
+fn decodes_valid_values() {
+    for (input, expected) in VALID {
+        let value = Value::decode(input)
+            .unwrap_or_else(|e| panic!("{:?}: {}", String::from_utf8_lossy(input), e));
+
+        assert_eq!(value, expected(), "{:?}", String::from_utf8_lossy(input));
+    }
+}
+
+#[test]
+fn encodes_back_to_the_same_bytes() {
+    for (input, expected) in VALID {
+        assert_eq!(
+            expected().encode(),
+            *input,
+            "{:?}",
+            String::from_utf8_lossy(input)
+        );
+    }
+}
+
+#[test]
+fn encodes_dict_keys_sorted() {
+    let value = Value::dict([("b", Value::Int(2)), ("a", Value::Int(1))]);
+
+    assert_eq!(value.encode(), b"d1:ai1e1:bi2ee");
+    assert_eq!(Value::decode(&value.encode()).unwrap(), value);
+}
+
+#[test]
+fn rejects_malformed_input() {
+    for (name, input) in INVALID {
+        assert!(Value::decode(input).is_err(), "{} was accepted", name);
+    }
+}
+
+#[test]
+fn limits_nesting_depth() {
+    let nested = |depth: usize| {
+        let mut input = vec![b'l'; depth];
+        input.extend(vec![b'e'; depth]);
+        input
+    };
+
+    assert!(Value::decode(&nested(64)).is_ok());
+    assert!(Value::decode(&nested(66)).is_err());
+    assert!(Value::decode(&nested(100_000)).is_err());
+
+    let mut dicts = b"d1:a".repeat(100);
+    dicts.extend(b"i1e");
+    dicts.extend(b"e".repeat(100));
+
+    assert!(Value::decode(&dicts).is_err());
+}
+
+#[test]
+fn decodes_prefix_before_raw_data() {
+    let message = b"d8:msg_typei1e5:piecei0eeRAW PIECE DATA";
+
+    let (header, read) = Value::decode_prefix(message).unwrap();
+
+    assert_eq!(header.get("msg_type").and_then(Value::as_int), Some(1));
+    assert_eq!(header.get("piece").and_then(Value::as_int), Some(0));
+    assert_eq!(&message[read..], b"RAW PIECE DATA");
+}
@@ -0,0 +1,88 @@
+use oxidized_torrent::{
+    bencode::Value,
+    dht::{random_id, Crawler, Discovery},
+};
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
+use tokio::net::UdpSocket;
+
+async fn local_node(bootstrap: Vec<String>) -> (SocketAddr, Crawler) {
+    let node = Crawler::bind("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap()
+        .with_bootstrap(bootstrap)
+        .with_interval(Duration::from_millis(50))
+        .with_sampling(false);
+
+    (node.local_addr().unwrap(), node)
+}
+
+// Makes `node` store `info_hash` the same way a real peer announcing it would
+async fn announce(socket: &UdpSocket, node: SocketAddr, info_hash: [u8; 20]) {
+    let message = Value::dict([
+        ("t", Value::bytes("aa")),
+        ("y", Value::bytes("q")),
+        ("q", Value::bytes("announce_peer")),
+        (
+            "a",
+            Value::dict([
+                ("id", Value::bytes(random_id())),
+                ("info_hash", Value::bytes(info_hash)),
+                ("port", Value::Int(6881)),
+                ("token", Value::bytes("token")),
+            ]),
+        ),
+    ]);
+
+    socket.send_to(&message.encode(), node).await.unwrap();
+}
+
+#[tokio::test]
+async fn crawler_samples_info_hashes_from_local_nodes() {
+    let (root_addr, root) = local_node(vec![]).await;
+    let (a_addr, a) = local_node(vec![root_addr.to_string()]).await;
+    let (b_addr, b) = local_node(vec![root_addr.to_string()]).await;
+
+    let mut seeded = HashSet::new();
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    for node in [root_addr, a_addr, b_addr] {
+        for _ in 0..5 {
+            let info_hash = random_id();
+
+            announce(&peer, node, info_hash).await;
+            seeded.insert(info_hash);
+        }
+    }
+
+    let _root = root.start();
+    let _a = a.start();
+    let _b = b.start();
+
+    // let a and b introduce themselves to the root node, so it can hand them out as neighbours
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut discoveries = Crawler::bind("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap()
+        .with_bootstrap(vec![root_addr.to_string()])
+        .with_interval(Duration::from_millis(50))
+        .start();
+
+    let mut sampled = HashSet::new();
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(discovery) = discoveries.recv().await {
+            if let Discovery::Samples(samples) = discovery {
+                sampled.extend(samples);
+            }
+
+            if sampled.is_superset(&seeded) {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("crawler did not sample every seeded info hash in time");
+
+    assert_eq!(sampled, seeded);
+}
@@ -0,0 +1,115 @@
+use oxidized_torrent::{bencode::Value, metadata::Metadata};
+
+fn file(path: &[&str], length: i64) -> Value {
+    Value::dict([
+        ("length", Value::Int(length)),
+        ("path", Value::List(path.iter().map(Value::bytes).collect())),
+    ])
+}
+
+#[test]
+fn parses_single_file_info() {
+    let info = Value::dict([
+        ("name", Value::bytes("ubuntu.iso")),
+        ("length", Value::Int(4_000_000_000)),
+        ("piece length", Value::Int(262_144)),
+        ("pieces", Value::bytes([0u8; 20])),
+    ]);
+
+    let metadata = Metadata::from_info(&info.encode()).unwrap();
+
+    assert_eq!(metadata.name, "ubuntu.iso");
+    assert_eq!(metadata.files.len(), 1);
+    assert_eq!(metadata.files[0].path, "ubuntu.iso");
+    assert_eq!(metadata.files[0].length, 4_000_000_000);
+}
+
+#[test]
+fn parses_multi_file_info() {
+    let info = Value::dict([
+        ("name", Value::bytes("Album")),
+        (
+            "files",
+            Value::List(vec![
+                file(&["CD1", "01 - Intro.flac"], 1000),
+                file(&["cover.jpg"], 20),
+            ]),
+        ),
+    ]);
+
+    let metadata = Metadata::from_info(&info.encode()).unwrap();
+
+    assert_eq!(metadata.name, "Album");
+
+    let files: Vec<(&str, i64)> = metadata
+        .files
+        .iter()
+        .map(|file| (file.path.as_str(), file.length))
+        .collect();
+
+    assert_eq!(files, [("CD1/01 - Intro.flac", 1000), ("cover.jpg", 20)]);
+}
+
+#[test]
+fn prefers_utf8_names() {
+    let info = Value::dict([
+        ("name", Value::bytes([0xff, 0xfe])),
+        ("name.utf-8", Value::bytes("Café")),
+        (
+            "files",
+            Value::List(vec![Value::dict([
+                ("length", Value::Int(1)),
+                ("path", Value::List(vec![Value::bytes([0xff])])),
+                ("path.utf-8", Value::List(vec![Value::bytes("été.txt")])),
+            ])]),
+        ),
+    ]);
+
+    let metadata = Metadata::from_info(&info.encode()).unwrap();
+
+    assert_eq!(metadata.name, "Café");
+    assert_eq!(metadata.files[0].path, "été.txt");
+}
+
+#[test]
+fn rejects_invalid_info() {
+    let cases = [
+        ("not bencode", b"not bencode".to_vec()),
+        ("truncated", b"d4:name3:ab".to_vec()),
+        ("not a dict", Value::List(vec![]).encode()),
+        ("no name", Value::dict([("length", Value::Int(1))]).encode()),
+        (
+            "no length or files",
+            Value::dict([("name", Value::bytes("a"))]).encode(),
+        ),
+        (
+            "file without length",
+            Value::dict([
+                ("name", Value::bytes("a")),
+                (
+                    "files",
+                    Value::List(vec![Value::dict([(
+                        "path",
+                        Value::List(vec![Value::bytes("b")]),
+                    )])]),
+                ),
+            ])
+            .encode(),
+        ),
+        (
+            "file without path",
+            Value::dict([
+                ("name", Value::bytes("a")),
+                (
+                    "files",
+                    Value::List(vec![Value::dict([("length", Value::Int(1))])]),
+                ),
+            ])
+            .encode(),
+        ),
+    ];
+
+    for (name, info) in cases {
+        assert!(Metadata::from_info(&info).is_err(), "{} was accepted", name);
+    }
+}
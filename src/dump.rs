@@ -0,0 +1,656 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use oxidized_entity::{info_hash::InfoHash, torrent, torrent::File};
+use oxidized_service::{sea_orm::DbConn, Mutation, NewTorrent, Query};
+use oxidized_torrent::torrent_file;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use tracing::info;
+
+const BATCH_SIZE: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // one JSON object per line, with the keys bitmagnet imports
+    Ndjson,
+    // the torrents columns of a magnetico database, without files
+    Csv,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> anyhow::Result<Self> {
+        match format {
+            "ndjson" | "jsonl" | "json" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            _ => Err(anyhow::anyhow!("Unknown dump format: {}", format)),
+        }
+    }
+
+    // "dump.csv.gz" is gzipped CSV, anything else is NDJSON
+    pub fn from_path(path: &str) -> Self {
+        match path.trim_end_matches(".gz").ends_with(".csv") {
+            true => Format::Csv,
+            false => Format::Ndjson,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonRecord {
+    #[serde(alias = "info_hash")]
+    info_hash: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, alias = "total_size")]
+    size: i64,
+    #[serde(default = "default_source")]
+    source: String,
+    #[serde(default, alias = "published_at")]
+    published_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<JsonFile>,
+    // base64 of the bencoded info dictionary, so the importing instance can serve .torrent files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonFile {
+    path: String,
+    size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CsvRecord {
+    info_hash: String,
+    name: Option<String>,
+    total_size: i64,
+    // unix timestamp
+    discovered_on: Option<i64>,
+}
+
+fn default_source() -> String {
+    "oxidized".to_string()
+}
+
+impl JsonRecord {
+    fn new(torrent: torrent::Model, info: Option<Vec<u8>>) -> Self {
+        JsonRecord {
+            info_hash: torrent.info_hash,
+            name: torrent.name,
+            size: torrent.size,
+            source: default_source(),
+            published_at: Some(torrent.added_at.and_utc()),
+            files: torrent
+                .files
+                .0
+                .into_iter()
+                .map(|file| JsonFile {
+                    path: file.path,
+                    size: file.length,
+                })
+                .collect(),
+            info: info.map(|info| BASE64.encode(info)),
+        }
+    }
+}
+
+impl From<torrent::Model> for CsvRecord {
+    fn from(torrent: torrent::Model) -> Self {
+        CsvRecord {
+            info_hash: torrent.info_hash,
+            name: torrent.name,
+            total_size: torrent.size,
+            discovered_on: Some(torrent.added_at.and_utc().timestamp()),
+        }
+    }
+}
+
+impl TryFrom<JsonRecord> for NewTorrent {
+    type Error = base64::DecodeError;
+
+    fn try_from(record: JsonRecord) -> Result<Self, Self::Error> {
+        Ok(NewTorrent {
+            info_hash: record.info_hash,
+            name: record.name.filter(|name| !name.is_empty()),
+            size: record.size,
+            files: record
+                .files
+                .into_iter()
+                .map(|file| File::new(file.path, file.size))
+                .collect(),
+            added_at: record.published_at.map(|at| at.naive_utc()),
+            info: record.info.map(|info| BASE64.decode(info)).transpose()?,
+        })
+    }
+}
+
+impl From<CsvRecord> for NewTorrent {
+    fn from(record: CsvRecord) -> Self {
+        NewTorrent {
+            info_hash: record.info_hash,
+            name: record.name.filter(|name| !name.is_empty()),
+            size: record.total_size,
+            files: vec![],
+            added_at: record
+                .discovered_on
+                .and_then(|at| DateTime::from_timestamp(at, 0))
+                .map(|at| at.naive_utc()),
+            info: None,
+        }
+    }
+}
+
+// Gzipped when the path ends in .gz, "-" is stdout
+enum Output {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gzip(GzEncoder<BufWriter<Box<dyn Write>>>),
+}
+
+impl Output {
+    fn create(path: &str) -> io::Result<Self> {
+        let inner: Box<dyn Write> = match path {
+            "-" => Box::new(io::stdout()),
+            _ => Box::new(std::fs::File::create(path)?),
+        };
+
+        Ok(match path.ends_with(".gz") {
+            true => Output::Gzip(GzEncoder::new(
+                BufWriter::new(inner),
+                Compression::default(),
+            )),
+            false => Output::Plain(BufWriter::new(inner)),
+        })
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(mut writer) => writer.flush(),
+            Output::Gzip(writer) => writer.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(writer) => writer.write(buf),
+            Output::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(writer) => writer.flush(),
+            Output::Gzip(writer) => writer.flush(),
+        }
+    }
+}
+
+// Gzipped when the path ends in .gz, "-" is stdin
+fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let inner: Box<dyn io::Read> = match path {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(std::fs::File::open(path)?),
+    };
+
+    Ok(match path.ends_with(".gz") {
+        true => Box::new(BufReader::new(MultiGzDecoder::new(inner))),
+        false => Box::new(BufReader::new(inner)),
+    })
+}
+
+enum RecordWriter {
+    Ndjson(Output),
+    Csv(Box<csv::Writer<Output>>),
+}
+
+impl RecordWriter {
+    fn write(&mut self, torrent: torrent::Model, info: Option<Vec<u8>>) -> anyhow::Result<()> {
+        match self {
+            RecordWriter::Ndjson(output) => {
+                serde_json::to_writer(&mut *output, &JsonRecord::new(torrent, info))?;
+                output.write_all(b"\n")?;
+            }
+            RecordWriter::Csv(writer) => writer.serialize(CsvRecord::from(torrent))?,
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        let output = match self {
+            RecordWriter::Ndjson(output) => output,
+            RecordWriter::Csv(writer) => writer.into_inner().map_err(|e| e.into_error())?,
+        };
+
+        Ok(output.finish()?)
+    }
+}
+
+// Streams every torrent to `path`, a batch at a time so the table never has to fit in memory
+pub async fn export(db: &DbConn, path: &str, format: Format) -> anyhow::Result<u64> {
+    let output = Output::create(path)?;
+    let mut writer = match format {
+        Format::Ndjson => RecordWriter::Ndjson(output),
+        Format::Csv => RecordWriter::Csv(Box::new(csv::Writer::from_writer(output))),
+    };
+
+    let mut last_id = 0;
+    let mut exported = 0;
+
+    loop {
+        let torrents = Query::find_torrents_after(db, last_id, BATCH_SIZE).await?;
+
+        let Some(last) = torrents.last() else {
+            break;
+        };
+
+        last_id = last.id;
+        exported += torrents.len() as u64;
+
+        let mut infos: HashMap<i32, Vec<u8>> = match format {
+            Format::Ndjson => {
+                let ids = torrents.iter().map(|torrent| torrent.id).collect();
+
+                Query::find_torrents_metadata(db, ids)
+                    .await?
+                    .into_iter()
+                    .map(|metadata| (metadata.torrent_id, metadata.info))
+                    .collect()
+            }
+            Format::Csv => HashMap::new(),
+        };
+
+        for torrent in torrents {
+            let info = infos.remove(&torrent.id);
+
+            writer.write(torrent, info)?;
+        }
+
+        info!("Exported {} torrents", exported);
+    }
+
+    writer.finish()?;
+
+    Ok(exported)
+}
+
+#[derive(Default)]
+pub struct ImportResult {
+    pub inserted: u64,
+    pub duplicate: u64,
+    pub invalid: u64,
+}
+
+// records that cannot be parsed are None, reading errors end the import
+type Records = Box<dyn Iterator<Item = io::Result<Option<NewTorrent>>>>;
+
+fn read_records(input: Box<dyn BufRead>, format: Format) -> Records {
+    match format {
+        Format::Ndjson => Box::new(
+            input
+                .lines()
+                .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+                .map(|line| {
+                    line.map(|line| {
+                        serde_json::from_str::<JsonRecord>(&line)
+                            .ok()
+                            .and_then(|record| NewTorrent::try_from(record).ok())
+                    })
+                }),
+        ),
+        Format::Csv => Box::new(
+            csv::Reader::from_reader(input)
+                .into_deserialize::<CsvRecord>()
+                .map(|record| match record {
+                    Ok(record) => Ok(Some(NewTorrent::from(record))),
+                    Err(e) if e.is_io_error() => Err(io::Error::other(e)),
+                    Err(_) => Ok(None),
+                }),
+        ),
+    }
+}
+
+// Up to BATCH_SIZE valid torrents, counting the invalid ones and those repeated within the
+// batch. None once the records run out.
+fn next_batch(
+    records: &mut Records,
+    result: &mut ImportResult,
+) -> io::Result<Option<Vec<NewTorrent>>> {
+    let mut batch = vec![];
+    let mut seen = HashSet::new();
+    let mut read = 0;
+
+    for record in records.by_ref().take(BATCH_SIZE as usize) {
+        read += 1;
+
+        let Some(mut torrent) = record? else {
+            result.invalid += 1;
+            continue;
+        };
+
+        let Ok(info_hash) = InfoHash::parse(&torrent.info_hash) else {
+            result.invalid += 1;
+            continue;
+        };
+
+        torrent.info_hash = info_hash.to_string();
+
+        // an info dictionary that is not the torrent's would be served as its .torrent file
+        if let Some(info) = &torrent.info {
+            if !matches!(info_hash, InfoHash::V1(_))
+                || torrent_file::info_hash(info) != torrent.info_hash
+            {
+                result.invalid += 1;
+                continue;
+            }
+        }
+
+        if !seen.insert(torrent.info_hash.clone()) {
+            result.duplicate += 1;
+            continue;
+        }
+
+        batch.push(torrent);
+    }
+
+    Ok((read > 0).then_some(batch))
+}
+
+// Reads a dump made by `export` (or bitmagnet/magnetico style records) and inserts it in
+// batches. Torrents that are already indexed are skipped.
+pub async fn import(db: &DbConn, path: &str, format: Format) -> anyhow::Result<ImportResult> {
+    let mut records = read_records(open(path)?, format);
+    let mut result = ImportResult::default();
+
+    while let Some(batch) = next_batch(&mut records, &mut result)? {
+        let valid = batch.len() as u64;
+        let inserted = Mutation::import_torrents(db, batch).await?;
+
+        result.inserted += inserted;
+        result.duplicate += valid - inserted;
+
+        info!(
+            "Imported {} torrents, {} duplicate, {} invalid",
+            result.inserted, result.duplicate, result.invalid
+        );
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use oxidized_entity::torrent::{Files, Trackers};
+
+    const HASH: &str = "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A";
+    const OTHER_HASH: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+    const V2_HASH: &str = "181226B2D004B8F1BA9DB868228A9768587C9F6D8CE606E85B015E9DCA415523";
+    const INFO: &[u8] = b"d6:lengthi3000e4:name10:ubuntu.iso12:piece lengthi16384e6:pieces0:e";
+
+    fn records(input: &str, format: Format) -> Records {
+        read_records(Box::new(io::Cursor::new(input.to_string())), format)
+    }
+
+    // every batch of `input`, and the counts they leave behind
+    fn batches(input: &str, format: Format) -> (Vec<Vec<NewTorrent>>, ImportResult) {
+        let mut records = records(input, format);
+        let mut result = ImportResult::default();
+        let mut batches = vec![];
+
+        while let Some(batch) = next_batch(&mut records, &mut result).unwrap() {
+            batches.push(batch);
+        }
+
+        (batches, result)
+    }
+
+    fn added_at(hour: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn detects_format_from_path() {
+        let cases = [
+            ("dump.csv", Format::Csv),
+            ("dump.csv.gz", Format::Csv),
+            ("/tmp/magnetico.csv", Format::Csv),
+            ("dump.ndjson", Format::Ndjson),
+            ("dump.jsonl.gz", Format::Ndjson),
+            ("dump.gz", Format::Ndjson),
+            ("csv", Format::Ndjson),
+            ("-", Format::Ndjson),
+        ];
+
+        for (path, format) in cases {
+            assert_eq!(Format::from_path(path), format, "{}", path);
+        }
+    }
+
+    #[test]
+    fn parses_format_names() {
+        for name in ["ndjson", "jsonl", "json"] {
+            assert_eq!(Format::parse(name).unwrap(), Format::Ndjson);
+        }
+
+        assert_eq!(Format::parse("csv").unwrap(), Format::Csv);
+
+        for name in ["", "CSV", "xml", "csv.gz"] {
+            assert!(Format::parse(name).is_err(), "{} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn reads_bitmagnet_records() {
+        let line = format!(
+            r#"{{"infoHash":"{}","name":"Ubuntu","size":3000,"source":"dht","publishedAt":"2024-03-01T12:00:00Z","files":[{{"path":"ubuntu.iso","size":3000}}]}}"#,
+            HASH.to_lowercase()
+        );
+
+        let torrent = records(&line, Format::Ndjson)
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(torrent.info_hash, HASH.to_lowercase());
+        assert_eq!(torrent.name.as_deref(), Some("Ubuntu"));
+        assert_eq!(torrent.size, 3000);
+        assert_eq!(torrent.files, [File::new("ubuntu.iso".to_string(), 3000)]);
+        assert_eq!(torrent.added_at, Some(added_at(12)));
+    }
+
+    #[test]
+    fn reads_snake_case_records() {
+        let line = format!(
+            r#"{{"info_hash":"{}","name":"Ubuntu","total_size":3000,"published_at":"2024-03-01T12:00:00Z"}}"#,
+            HASH
+        );
+
+        let torrent = records(&line, Format::Ndjson)
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(torrent.info_hash, HASH);
+        assert_eq!(torrent.size, 3000);
+        assert!(torrent.files.is_empty());
+        assert_eq!(torrent.added_at, Some(added_at(12)));
+    }
+
+    #[test]
+    fn reads_magnetico_csv() {
+        let input = format!(
+            "info_hash,name,total_size,discovered_on\n{},Ubuntu,3000,1709294400\n{},,0,\n",
+            HASH, OTHER_HASH
+        );
+
+        let torrents: Vec<NewTorrent> = records(&input, Format::Csv)
+            .map(|record| record.unwrap().unwrap())
+            .collect();
+
+        assert_eq!(torrents[0].info_hash, HASH);
+        assert_eq!(torrents[0].name.as_deref(), Some("Ubuntu"));
+        assert_eq!(torrents[0].size, 3000);
+        assert_eq!(torrents[0].added_at, Some(added_at(12)));
+
+        // an empty name still needs its info fetched
+        assert_eq!(torrents[1].name, None);
+        assert_eq!(torrents[1].added_at, None);
+    }
+
+    #[test]
+    fn treats_empty_names_as_missing() {
+        let line = format!(r#"{{"infoHash":"{}","name":""}}"#, HASH);
+
+        let torrent = records(&line, Format::Ndjson)
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(torrent.name, None);
+        assert_eq!(torrent.size, 0);
+        assert_eq!(torrent.added_at, None);
+    }
+
+    #[test]
+    fn counts_invalid_and_duplicate_records() {
+        let input = [
+            format!(r#"{{"infoHash":"{}","name":"a"}}"#, HASH.to_lowercase()),
+            // repeated in another case
+            format!(r#"{{"infoHash":"{}","name":"a"}}"#, HASH),
+            String::new(),
+            format!(r#"{{"infoHash":"{}"}}"#, OTHER_HASH),
+            // bad hash, not JSON, no hash at all
+            r#"{"infoHash":"not a hash","name":"a"}"#.to_string(),
+            "not json".to_string(),
+            r#"{"name":"a"}"#.to_string(),
+            // stored even though its metadata cannot be fetched yet
+            format!(r#"{{"infoHash":"{}"}}"#, V2_HASH),
+        ]
+        .join("\n");
+
+        let (batches, result) = batches(&input, Format::Ndjson);
+
+        let hashes: Vec<&str> = batches[0].iter().map(|t| t.info_hash.as_str()).collect();
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(hashes, [HASH, OTHER_HASH, V2_HASH]);
+        assert_eq!(result.duplicate, 1);
+        assert_eq!(result.invalid, 3);
+        assert_eq!(result.inserted, 0);
+    }
+
+    #[test]
+    fn counts_invalid_csv_rows() {
+        let input = format!(
+            "info_hash,name,total_size,discovered_on\n{},a,1,\nnot a hash,b,1,\n{},c,not a size,\n",
+            HASH, OTHER_HASH
+        );
+
+        let (batches, result) = batches(&input, Format::Csv);
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 1);
+        assert_eq!(result.invalid, 2);
+    }
+
+    #[test]
+    fn splits_records_into_batches() {
+        let input = (0..=BATCH_SIZE)
+            .map(|i| format!(r#"{{"infoHash":"{:040X}","name":"a"}}"#, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (batches, _) = batches(&input, Format::Ndjson);
+
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+
+        assert_eq!(sizes, [BATCH_SIZE as usize, 1]);
+    }
+
+    #[test]
+    fn reads_back_exported_records() {
+        let torrent = torrent::Model {
+            id: 1,
+            name: Some("Ubuntu".to_string()),
+            info_hash: torrent_file::info_hash(INFO),
+            size: 3000,
+            files: Files(vec![File::new("ubuntu.iso".to_string(), 3000)]),
+            added_at: added_at(12),
+            seeders: 0,
+            leechers: 0,
+            trackers: Trackers(vec![]),
+            category: 0,
+            release: None,
+            last_scrape: None,
+            last_tracker_scrape: None,
+            last_stale: None,
+            leased_until: None,
+            metadata_attempts: 0,
+            next_attempt_at: None,
+            metadata_error: None,
+        };
+
+        let record = JsonRecord::new(torrent.clone(), Some(INFO.to_vec()));
+        let line = serde_json::to_string(&record).unwrap();
+        let json = records(&line, Format::Ndjson)
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(CsvRecord::from(torrent.clone())).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let csv = records(&csv, Format::Csv).next().unwrap().unwrap().unwrap();
+
+        for imported in [&json, &csv] {
+            assert_eq!(imported.info_hash, torrent.info_hash);
+            assert_eq!(imported.name, torrent.name);
+            assert_eq!(imported.size, torrent.size);
+            assert_eq!(imported.added_at, Some(torrent.added_at));
+        }
+
+        assert_eq!(json.files, torrent.files.0);
+        assert_eq!(json.info.as_deref(), Some(INFO));
+        assert_eq!(csv.info, None);
+    }
+
+    #[test]
+    fn checks_info_against_the_hash() {
+        let info_hash = torrent_file::info_hash(INFO);
+        let info = BASE64.encode(INFO);
+
+        let input = [
+            format!(
+                r#"{{"infoHash":"{}","name":"a","info":"{}"}}"#,
+                info_hash, info
+            ),
+            // the info of another torrent, and not base64
+            format!(r#"{{"infoHash":"{}","name":"b","info":"{}"}}"#, HASH, info),
+            format!(
+                r#"{{"infoHash":"{}","name":"c","info":"not base64!"}}"#,
+                OTHER_HASH
+            ),
+        ]
+        .join("\n");
+
+        let (batches, result) = batches(&input, Format::Ndjson);
+
+        assert_eq!(batches[0].len(), 1);
+        assert_eq!(batches[0][0].info.as_deref(), Some(INFO));
+        assert_eq!(result.invalid, 2);
+    }
+}
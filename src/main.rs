@@ -1,3 +1,229 @@
+mod dump;
+
+use clap::{Args, Parser, Subcommand};
+use dump::Format;
+use oxidized_api::{MiscTasksService, Supervisor, TorrentService};
+use oxidized_config::try_get_config;
+use oxidized_migration::{Migrator, MigratorTrait};
+use oxidized_service::sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use oxidized_service::Query;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{info, Level};
+
+#[derive(Parser)]
+#[command(version, about = "A torrent indexer for the BitTorrent DHT")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the HTTP API along with the spider and workers enabled in the config (default)
+    Serve {
+        /// Only serve HTTP, leaving the spider and workers to other processes
+        #[arg(long)]
+        http_only: bool,
+    },
+    /// Apply, revert or list database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Crawl the DHT and queue new torrents, without the HTTP API
+    SpiderOnly,
+    /// Fetch metadata, scrape trackers and run maintenance tasks, without the HTTP API
+    Worker,
+    /// Print index statistics as JSON
+    Stats,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Write every torrent to a dump. Only NDJSON keeps the info dictionaries .torrent files are
+    /// built from, torrents imported from CSV are served as magnet links.
+    Export(DumpArgs),
+    /// Add the torrents in a dump, skipping those already indexed
+    Import(DumpArgs),
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Number of migrations to apply, all pending by default
+        steps: Option<u32>,
+    },
+    /// Revert applied migrations
+    Down {
+        /// Number of migrations to revert
+        #[arg(default_value_t = 1)]
+        steps: u32,
+    },
+    /// List migrations and whether they are applied
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Validate the configuration and check the database is reachable
+    Check,
+}
+
+#[derive(Args)]
+struct DumpArgs {
+    /// Gzipped when it ends in .gz, - is stdout or stdin
+    file: String,
+    /// ndjson or csv, defaults to CSV for .csv files and NDJSON otherwise
+    #[arg(long, value_parser = Format::parse)]
+    format: Option<Format>,
+}
+
 fn main() {
-    let _ = oxidized_api::main();
+    let cli = Cli::parse();
+
+    // logs go to stderr so dumps and stats can be written to stdout
+    tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let result = match cli.command.unwrap_or(Command::Serve { http_only: false }) {
+        Command::Serve { http_only } => oxidized_api::serve(http_only),
+        command => tokio::runtime::Runtime::new()
+            .map_err(Into::into)
+            .and_then(|runtime| runtime.block_on(run(command))),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Serve { .. } => unreachable!("serve runs on the Rocket runtime"),
+        Command::Migrate { action } => {
+            let db = connect().await?;
+
+            match action {
+                MigrateAction::Up { steps } => Migrator::up(&db, steps).await?,
+                MigrateAction::Down { steps } => Migrator::down(&db, Some(steps)).await?,
+                MigrateAction::Status => Migrator::status(&db).await?,
+            }
+        }
+        Command::SpiderOnly => {
+            if !try_get_config()?.app.spider {
+                anyhow::bail!("The spider is disabled in the config (app.spider)");
+            }
+
+            let db = connect().await?;
+            let supervisor = Supervisor::new();
+
+            TorrentService::new(supervisor.clone())
+                .with_workers(false)
+                .start(db)
+                .await;
+
+            wait_for_shutdown(&supervisor).await?;
+        }
+        Command::Worker => {
+            let config = try_get_config()?;
+
+            if !config.app.update_info && !config.app.update_trackers {
+                anyhow::bail!(
+                    "Both workers are disabled in the config (app.update_info, app.update_trackers)"
+                );
+            }
+
+            let db = connect().await?;
+            let supervisor = Supervisor::new();
+
+            TorrentService::new(supervisor.clone())
+                .with_spider(false)
+                .start(db.clone())
+                .await;
+            MiscTasksService::new(supervisor.clone()).start(db);
+
+            wait_for_shutdown(&supervisor).await?;
+        }
+        Command::Stats => {
+            let db = connect().await?;
+            let stats = Query::get_stats(&db).await?;
+
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+        Command::Config {
+            action: ConfigAction::Check,
+        } => check_config().await?,
+        Command::Export(args) => {
+            let db = connect().await?;
+            let format = args.format.unwrap_or(Format::from_path(&args.file));
+            let exported = dump::export(&db, &args.file, format).await?;
+
+            info!("Exported {} torrents to {}", exported, args.file);
+        }
+        Command::Import(args) => {
+            let db = connect().await?;
+            let format = args.format.unwrap_or(Format::from_path(&args.file));
+
+            // a fresh instance can be bootstrapped from a dump
+            Migrator::up(&db, None).await?;
+
+            let result = dump::import(&db, &args.file, format).await?;
+
+            info!(
+                "Imported {} torrents from {}, {} were duplicates and {} invalid",
+                result.inserted, args.file, result.duplicate, result.invalid
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_config() -> anyhow::Result<()> {
+    let config = try_get_config()?;
+
+    let db = connect().await?;
+    db.ping().await?;
+
+    println!("Configuration is valid and the database is reachable");
+    println!("  spider:            {}", config.app.spider);
+    println!("  sample_infohashes: {}", config.app.sample_infohashes);
+    println!("  update_info:       {}", config.app.update_info);
+    println!("  info_concurrency:  {}", config.app.info_concurrency);
+    println!("  update_trackers:   {}", config.app.update_trackers);
+    println!("  clean:             {}", config.app.clean);
+    println!("  filter_nsfw:       {}", config.app.filter_nsfw);
+    println!("  apikey:            {}", config.auth.apikey.is_some());
+
+    Ok(())
+}
+
+// SIGINT from a terminal, SIGTERM from systemd, Kubernetes or docker stop
+async fn wait_for_shutdown(supervisor: &Supervisor) -> anyhow::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+
+    info!("Shutting down");
+
+    supervisor.shutdown().await;
+
+    Ok(())
+}
+
+async fn connect() -> anyhow::Result<DatabaseConnection> {
+    let config = try_get_config()?;
+
+    let mut options = ConnectOptions::new(config.database.url);
+    options.sqlx_logging(config.database.sqlx_logging);
+
+    Ok(Database::connect(options).await?)
 }